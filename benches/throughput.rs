@@ -0,0 +1,30 @@
+//! Raw interpreter throughput, with I/O and timing out of the way.
+//!
+//! Run with `cargo bench --bench throughput`.
+
+use std::time::Instant;
+
+use chip8_emulator::virtual_machine::{Chip8, Chip8Error};
+
+
+const CYCLES: usize = 10_000_000;
+
+fn main() -> Result<(), Chip8Error> {
+    for name in ["pong2", "tetris", "invaders"] {
+        let rom = std::fs::read(format!("programs/{}.c8", name))?;
+        let mut chip = Chip8::from_rom_bytes(&rom)?.with_rng_seed(1);
+
+        let started = Instant::now();
+        chip.bench_run(CYCLES)?;
+        let elapsed = started.elapsed();
+
+        println!(
+            "{:<10} {:>12} instructions in {:>8.2?}  ({:.1} M/s)",
+            name,
+            chip.cycles(),
+            elapsed,
+            chip.cycles() as f64 / elapsed.as_secs_f64() / 1e6,
+        );
+    }
+    Ok(())
+}
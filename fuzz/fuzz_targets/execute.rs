@@ -0,0 +1,26 @@
+//! Runs arbitrary bytes as a ROM for a while. Errors are fine, panics are bugs.
+//!
+//! With cargo-fuzz installed (`cargo install cargo-fuzz`, needs a nightly toolchain):
+//!
+//! ```sh
+//! cargo +nightly fuzz run execute fuzz/corpus/execute
+//! ```
+//!
+//! The corpus starts out with a couple of bundled ROMs. Crashing inputs are saved
+//! under `fuzz/artifacts/execute/` and can be replayed by passing one instead of the corpus.
+#![no_main]
+
+use chip8_emulator::virtual_machine::Chip8;
+use libfuzzer_sys::fuzz_target;
+
+/// Enough to get through a few frames without making each run slow.
+const MAX_CYCLES: usize = 1_000;
+
+fuzz_target!(|rom: &[u8]| {
+    // A fixed seed, so that crashes involving CXNN reproduce.
+    let Ok(chip) = Chip8::from_rom_bytes(rom) else {
+        return;
+    };
+    let mut chip = chip.with_rng_seed(0);
+    let _ = chip.run_cycles(MAX_CYCLES);
+});
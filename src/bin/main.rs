@@ -2,6 +2,8 @@ use std::path::{Path, PathBuf};
 use std::error::Error;
 
 use clap::Parser;
+use chip8_emulator::data_structures::HexKeyMap;
+use chip8_emulator::virtual_machine::{BundledRoms, Chip8, OpLiteral};
 
 
 #[derive(Parser, Debug)]
@@ -13,20 +15,76 @@ pub struct Args {
             help="The path to the chip-8 program to run.",
         )
     ]
-    program: Option<PathBuf>
+    program: Option<PathBuf>,
+
+    #[arg(long, help = "Check the program for problems and suggest quirks for it, instead of running it.")]
+    lint: bool,
+
+    #[arg(long, help = "List the supported instructions and what they do, instead of running a program.")]
+    opcodes: bool,
+
+    #[arg(long, help = "A TOML file mapping keyboard keys to keypad digits, like `\"q\" = 0x4`.")]
+    keymap_file: Option<PathBuf>,
+
+    #[arg(long, help = "Ring the terminal bell whenever the program starts a beep.")]
+    bell: bool,
+
+    #[cfg(feature = "tui")]
+    #[arg(long, help = "Open the program in the terminal debugger.")]
+    debug: bool,
 }
 
 
 fn main() -> Result<(), Box<dyn Error>>{
     let args = Args::parse();
-    let mut my_chip = chip8_emulator::virtual_machine::Chip8::new();
-    if args.program.is_none() {
-        my_chip.load_program("pong2.c8");
-    } else {
-        my_chip.load_program(args.program.unwrap());
+    if args.lint {
+        return lint(args.program.as_deref());
     }
-    my_chip.initialize();
-    my_chip.start()?;
+    if args.opcodes {
+        for literal in OpLiteral::all() {
+            println!("{}  {}", format!("{:?}", literal).trim_start_matches('_'), literal.description());
+        }
+        return Ok(());
+    }
+
+    let keymap = match &args.keymap_file {
+        Some(path) => HexKeyMap::from_toml_path(path)?,
+        None => HexKeyMap::default(),
+    };
 
+    let mut my_chip = match args.program {
+        Some(program) => Chip8::from_rom(program)?,
+        None => Chip8::from_rom_bytes(BundledRoms::get("pong2").unwrap())?,
+    };
+
+    #[cfg(feature = "tui")]
+    if args.debug {
+        chip8_emulator::tui::run_debugger(&mut my_chip)?;
+        return Ok(());
+    }
+    my_chip.start_with_keymap(keymap, args.bell)?;
+
+    Ok(())
+}
+
+fn lint(program: Option<&Path>) -> Result<(), Box<dyn Error>> {
+    let bytes = match program {
+        Some(path) => match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(err) => path.to_str().and_then(BundledRoms::get).ok_or(err)?.to_vec(),
+        },
+        None => BundledRoms::get("pong2").unwrap().to_vec(),
+    };
+
+    for warning in Chip8::validate_rom(&bytes)? {
+        println!("warning: {:?}", warning);
+    }
+
+    let hints = Chip8::diagnose_quirks(&bytes);
+    println!("default quirks: {:?}", hints.default_run);
+    for (name, run) in &hints.runs {
+        println!("{:>16}: {:?}", name, run);
+    }
+    println!("suggested: {:?}", hints.suggested());
     Ok(())
 }
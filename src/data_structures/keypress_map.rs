@@ -1,5 +1,27 @@
 use std::collections::HashMap;
+use std::path::Path;
+use std::str::FromStr;
 use crossterm::event::{KeyCode};
+use thiserror::Error;
+
+
+#[derive(Debug, Error)]
+pub enum KeyMapError {
+    #[error("Line {line}: expected `\"key\" = hex digit`.")]
+    Syntax { line: usize },
+    #[error("Line {line}: `{key}` isn't a key name this keymap understands.")]
+    UnknownKey { line: usize, key: String },
+    #[error("Line {line}: `{value}` isn't a hex keypad digit from 0x0 to 0xF.")]
+    BadTarget { line: usize, value: String },
+    #[error("Line {line}: `{key}` is mapped twice.")]
+    DuplicateKey { line: usize, key: String },
+    #[error("Line {line}: keypad digit {target:#x} already has a key.")]
+    DuplicateTarget { line: usize, target: usize },
+    #[error("Keypad digit {0:#x} has no key.")]
+    Missing(usize),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
 
 
 #[derive(Debug)]
@@ -28,4 +50,138 @@ impl Default for HexKeyMap {
 
         Self(hmap)
     }
-}
\ No newline at end of file
+}
+
+impl HexKeyMap {
+    /// Reads a keymap from a TOML file, see `HexKeyMap::from_str` for the format.
+    pub fn from_toml_path<P: AsRef<Path>>(path: P) -> Result<Self, KeyMapError> {
+        std::fs::read_to_string(path)?.parse()
+    }
+}
+
+/// The key named in a keymap file: a single character, or one of a few special keys.
+fn parse_key(name: &str) -> Option<KeyCode> {
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => return Some(KeyCode::Char(c)),
+        (None, _) => return None,
+        _ => {},
+    }
+    match name.to_ascii_lowercase().as_str() {
+        "space" => Some(KeyCode::Char(' ')),
+        "enter" => Some(KeyCode::Enter),
+        "tab" => Some(KeyCode::Tab),
+        "backspace" => Some(KeyCode::Backspace),
+        "up" => Some(KeyCode::Up),
+        "down" => Some(KeyCode::Down),
+        "left" => Some(KeyCode::Left),
+        "right" => Some(KeyCode::Right),
+        _ => None,
+    }
+}
+
+fn parse_target(value: &str) -> Option<usize> {
+    let target = match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16).ok()?,
+        None => value.parse().ok()?,
+    };
+    (target < 16).then_some(target)
+}
+
+impl FromStr for HexKeyMap {
+    type Err = KeyMapError;
+
+    /// Parses the flat subset of TOML a keymap needs: one `"key" = digit` pair per line, like
+    /// `"q" = 0x4`, plus blank lines and `#` comments. Keys are single characters or `space`,
+    /// `enter`, `tab`, `backspace`, `up`, `down`, `left` and `right`. All 16 keypad digits must
+    /// have exactly one key.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut map = HashMap::new();
+        let mut targets = [false; 16];
+
+        for (idx, raw) in text.lines().enumerate() {
+            let line = idx + 1;
+            let content = raw.trim();
+            if content.is_empty() || content.starts_with('#') {
+                continue;
+            }
+            // The key is quoted and may itself be `#` or `=`, so only look for those after it.
+            let (key, rest) = content.strip_prefix('"')
+                .and_then(|quoted| quoted.split_once('"'))
+                .ok_or(KeyMapError::Syntax { line })?;
+            let value = rest.trim_start().strip_prefix('=').ok_or(KeyMapError::Syntax { line })?;
+            let value = value.split_once('#').map_or(value, |(before, _)| before).trim();
+
+            let code = parse_key(key).ok_or_else(|| KeyMapError::UnknownKey { line, key: key.to_string() })?;
+            let target = parse_target(value).ok_or_else(|| KeyMapError::BadTarget { line, value: value.to_string() })?;
+            if map.contains_key(&code) {
+                return Err(KeyMapError::DuplicateKey { line, key: key.to_string() });
+            }
+            if core::mem::replace(&mut targets[target], true) {
+                return Err(KeyMapError::DuplicateTarget { line, target });
+            }
+            map.insert(code, target);
+        }
+
+        match targets.iter().position(|mapped| !mapped) {
+            Some(missing) => Err(KeyMapError::Missing(missing)),
+            None => Ok(Self(map)),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The default layout, except that the arrow keys drive 2/4/6/8.
+    const ARROWS: &str = r#"
+        # Movement
+        "up" = 0x2
+        "left" = 0x4
+        "right" = 0x6
+        "down" = 0x8
+
+        "1" = 0x1
+        "3" = 0x3
+        "4" = 0xC
+        "q" = 0x0
+        "e" = 0x5
+        "r" = 0xD
+        "s" = 0x7
+        "d" = 0x9
+        "f" = 0xE
+        "z" = 0xA
+        "x" = 0xB
+        "c" = 15
+    "#;
+
+    #[test]
+    fn parses_a_keymap() -> Result<(), KeyMapError> {
+        let keymap: HexKeyMap = ARROWS.parse()?;
+        assert_eq!(keymap.0.len(), 16);
+        assert_eq!(keymap.0[&KeyCode::Up], 0x2);
+        assert_eq!(keymap.0[&KeyCode::Char('q')], 0x0);
+        assert_eq!(keymap.0[&KeyCode::Char('c')], 0xF);
+        Ok(())
+    }
+
+    #[test]
+    fn keys_can_be_comment_or_equals_signs() -> Result<(), KeyMapError> {
+        let symbols = ARROWS
+            .replace("\"1\" = 0x1", "\"#\" = 0x1 # hash")
+            .replace("\"3\" = 0x3", "\"=\"=0x3");
+        let keymap: HexKeyMap = symbols.parse()?;
+        assert_eq!(keymap.0[&KeyCode::Char('#')], 0x1);
+        assert_eq!(keymap.0[&KeyCode::Char('=')], 0x3);
+        assert!(matches!("\"a\" 0x1".parse::<HexKeyMap>(), Err(KeyMapError::Syntax { line: 1 })));
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_a_digit_mapped_twice() {
+        let twice = ARROWS.replace("\"q\" = 0x0", "\"q\" = 0x2");
+        assert!(matches!(twice.parse::<HexKeyMap>(), Err(KeyMapError::DuplicateTarget { line: 11, target: 2 })));
+    }
+}
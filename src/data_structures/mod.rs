@@ -1,5 +1,7 @@
 mod nibble;
 pub use nibble::*;
 
+#[cfg(feature = "std")]
 mod keypress_map;
-pub use keypress_map::*;
\ No newline at end of file
+#[cfg(feature = "std")]
+pub use keypress_map::*;
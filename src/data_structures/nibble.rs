@@ -1,5 +1,7 @@
 use core::fmt::Display;
 
+use alloc::format;
+
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[repr(u8)]
@@ -181,6 +183,24 @@ impl From<NibblePair> for u8 {
     }
 }
 
+/// The four nibbles of a 16-bit word, most significant first.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct NibbleQuad(pub [Nibble; 4]);
+
+impl From<u16> for NibbleQuad {
+    fn from(word: u16) -> Self {
+        let high: NibblePair = ((word >> 8) as u8).into();
+        let low: NibblePair = (word as u8).into();
+        Self([high.high, high.low, low.high, low.low])
+    }
+}
+
+impl NibbleQuad {
+    pub fn to_hex_chars(&self) -> [char; 4] {
+        self.0.map(|nibble| nibble.to_hex_char())
+    }
+}
+
 #[cfg(test)]
 pub mod tests {
     use super::*;
@@ -201,4 +221,11 @@ pub mod tests {
             assert_eq!(back_as_byte, full_byte);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn split_word_into_quad() {
+        let quad = NibbleQuad::from(0xABCD);
+        assert_eq!(quad.0, [Nibble::B1010, Nibble::B1011, Nibble::B1100, Nibble::B1101]);
+        assert_eq!(quad.to_hex_chars(), ['A', 'B', 'C', 'D']);
+    }
+}
@@ -1,2 +1,11 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
 pub mod virtual_machine;
-pub mod data_structures;
\ No newline at end of file
+pub mod data_structures;
+#[cfg(feature = "tui")]
+pub mod tui;
+
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_support;
@@ -0,0 +1,152 @@
+//! Test doubles for the [`Renderer`] and [`Audio`] traits, for front-ends
+//! that want to test against the interpreter without a real display or sound device,
+//! and golden traces that pin down exactly what a ROM does to the registers.
+
+use alloc::{format, string::String};
+
+use crate::virtual_machine::{Audio, Chip8, Chip8Error, Renderer, Screen};
+
+
+/// Set this environment variable to rewrite golden files instead of comparing against them.
+pub const REGENERATE_GOLDEN: &str = "CHIP8_REGENERATE_GOLDEN";
+
+
+/// Counts rendered frames and keeps a copy of the last one.
+#[derive(Debug, Default)]
+pub struct MockRenderer {
+    pub frames: usize,
+    pub last_screen: Option<Screen>,
+}
+
+impl Renderer for MockRenderer {
+    fn render(&mut self, screen: &Screen) {
+        self.frames += 1;
+        self.last_screen = Some(screen.clone());
+    }
+}
+
+/// Counts how many times the tone was started and stopped.
+#[derive(Debug, Default)]
+pub struct MockAudio {
+    pub starts: usize,
+    pub stops: usize,
+}
+
+impl MockAudio {
+    pub fn is_playing(&self) -> bool {
+        self.starts > self.stops
+    }
+}
+
+impl Audio for MockAudio {
+    fn start_tone(&mut self) {
+        self.starts += 1;
+    }
+    fn stop_tone(&mut self) {
+        self.stops += 1;
+    }
+}
+
+
+/// Runs `rom` for `cycles` instructions and lists each one as its address, its opcode, then
+/// every data register, `I` and the stack pointer right after it ran, one line per instruction.
+pub fn golden_trace(rom: &[u8], cycles: usize) -> Result<String, Chip8Error> {
+    let mut chip = Chip8::from_rom_bytes(rom)?;
+    let mut trace = String::new();
+    for _ in 0..cycles {
+        let (pc, word) = (chip.program_counter.read(), chip.fetch_opcode());
+        chip.step()?;
+        let registers: String = chip.data_registers.values().iter().map(|value| format!("{:02x}", value)).collect();
+        trace.push_str(&format!(
+            "{:#05x} {:04x} v={} i={:03x} sp={}\n",
+            pc, word, registers, chip.address_register.read(), chip.stack_pointer
+        ));
+    }
+    Ok(trace)
+}
+
+/// Compares `actual` against the golden file at `path`, panicking at the first line that
+/// differs. With `CHIP8_REGENERATE_GOLDEN` set, writes `actual` to `path` instead.
+#[cfg(feature = "std")]
+pub fn assert_golden(path: impl AsRef<std::path::Path>, actual: &str) {
+    let path = path.as_ref();
+    if std::env::var_os(REGENERATE_GOLDEN).is_some() {
+        std::fs::write(path, actual).unwrap_or_else(|err| panic!("Couldn't write {}: {}", path.display(), err));
+        return;
+    }
+    let expected = std::fs::read_to_string(path).unwrap_or_else(|err| {
+        panic!("Couldn't read {}: {}. Set {} to create it.", path.display(), err, REGENERATE_GOLDEN)
+    });
+    let mut expected_lines = expected.lines();
+    for (line, actual_line) in actual.lines().enumerate() {
+        let expected_line = expected_lines.next();
+        assert_eq!(
+            expected_line,
+            Some(actual_line),
+            "{} differs at line {}. Set {} if the change is intended.",
+            path.display(),
+            line + 1,
+            REGENERATE_GOLDEN
+        );
+    }
+    assert_eq!(expected_lines.next(), None, "{} has more lines than the trace.", path.display());
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn mocks_through_run_frame() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::new();
+        chip.initialize();
+        chip.load_program_bytes(&[
+            0x60, 0x05, // V0 = 5
+            0xF0, 0x18, // sound timer = V0
+            0xA0, 0x00, // I = glyph for 0
+            0xD1, 0x15, // draw it at (V1, V1)
+            0x12, 0x08, // loop forever
+        ]);
+
+        let mut renderer = MockRenderer::default();
+        let mut audio = MockAudio::default();
+
+        chip.run_frame(&mut renderer, &mut audio)?;
+        assert_eq!(renderer.frames, 1);
+        assert!(renderer.last_screen.as_ref().unwrap()[0]);
+        assert_eq!(audio.starts, 1);
+        assert!(audio.is_playing());
+
+        for _ in 0..4 {
+            chip.run_frame(&mut renderer, &mut audio)?;
+        }
+        assert_eq!(renderer.frames, 1);
+        assert_eq!(audio.stops, 1);
+        assert!(!audio.is_playing());
+        Ok(())
+    }
+
+    #[test]
+    fn arithmetic_rom_matches_its_golden_trace() -> Result<(), Chip8Error> {
+        let rom = [
+            0x60, 0xF0, // V0 = 0xF0
+            0x61, 0x20, // V1 = 0x20
+            0x80, 0x14, // V0 += V1, carries
+            0x82, 0x10, // V2 = V1
+            0x82, 0x05, // V2 -= V0
+            0x80, 0x06, // V0 >>= 1
+            0x82, 0x2E, // V2 <<= 1
+            0x85, 0x21, // V5 |= V2
+            0x85, 0x12, // V5 &= V1
+            0x85, 0x03, // V5 ^= V0
+            0x75, 0xFF, // V5 += 0xFF, wraps without touching VF
+            0xA3, 0x00, // I = 0x300
+            0xF5, 0x1E, // I += V5
+            0x12, 0x1A, // loop in place
+        ];
+        let trace = golden_trace(&rom, 15)?;
+        assert_golden(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/arithmetic.trace"), &trace);
+        Ok(())
+    }
+}
@@ -0,0 +1,149 @@
+//! A terminal debugger: the screen, registers, disassembly around the program counter and
+//! a memory pane, with keys to step, continue and toggle breakpoints.
+//!
+//! | Key          | Action                                    |
+//! |--------------|-------------------------------------------|
+//! | `s`          | Execute one instruction                   |
+//! | `c`          | Continue / pause at 60 frames per second  |
+//! | `b`          | Toggle a breakpoint at the program counter |
+//! | PgUp / PgDn  | Scroll the memory pane                    |
+//! | `q` / Esc    | Quit                                      |
+
+use std::time::Duration;
+
+use crossterm::event::{self, Event, KeyCode};
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout},
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Block, Borders, Paragraph},
+    Frame, Terminal,
+};
+
+use crate::virtual_machine::{Bus, Chip8, Chip8Error, DebugView, StopReason, TerminalGuard, MEMORY_SIZE};
+
+
+/// Instructions shown on either side of the program counter.
+const DISASSEMBLY_CONTEXT: u16 = 8;
+const MEMORY_ROWS: usize = 16;
+
+/// Runs the debugger until the user quits.
+pub fn run_debugger<B: Bus>(chip: &mut Chip8<B>) -> Result<StopReason, Chip8Error> {
+    let _terminal_guard = TerminalGuard::new()?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(std::io::stdout()))?;
+
+    let mut running = false;
+    let mut last_stop = None;
+    let mut memory_start = chip.register_snapshot().address_register & !0x7;
+
+    loop {
+        let view = chip.debug_view(DISASSEMBLY_CONTEXT, memory_start, MEMORY_ROWS);
+        terminal.draw(|frame| draw(frame, &view, running, last_stop))?;
+
+        if running {
+            // The debugger draws the screen itself, from the `DebugView`.
+            if let Some(reason) = chip.frame_advance(&mut (), &mut ())? {
+                running = false;
+                last_stop = Some(reason);
+            }
+        }
+
+        if !event::poll(Duration::from_secs(1) / 60)? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(StopReason::UserQuit),
+            KeyCode::Char('s') if !running => chip.step()?,
+            KeyCode::Char('c') => {
+                running = !running;
+                last_stop = None;
+            }
+            KeyCode::Char('b') => {
+                let pc = view.program_counter;
+                if chip.has_breakpoint(pc) {
+                    chip.remove_breakpoint(pc);
+                } else {
+                    chip.add_breakpoint(pc);
+                }
+            }
+            KeyCode::PageUp => memory_start = memory_start.saturating_sub(8 * MEMORY_ROWS as u16),
+            KeyCode::PageDown => memory_start = (memory_start + 8 * MEMORY_ROWS as u16).min(MEMORY_SIZE as u16 - 8 * MEMORY_ROWS as u16),
+            _ => {}
+        }
+    }
+}
+
+fn draw(frame: &mut Frame, view: &DebugView, running: bool, last_stop: Option<StopReason>) {
+    let [left, right] = split(Direction::Horizontal, frame.size(), [Constraint::Min(66), Constraint::Length(36)]);
+    let [screen, memory] = split(Direction::Vertical, left, [Constraint::Length(34), Constraint::Min(0)]);
+    let [registers, disassembly] = split(Direction::Vertical, right, [Constraint::Length(14), Constraint::Min(0)]);
+
+    let status = match (running, last_stop) {
+        (true, _) => String::from("running"),
+        (false, Some(reason)) => format!("stopped: {:?}", reason),
+        (false, None) => String::from("paused"),
+    };
+    frame.render_widget(
+        Paragraph::new(view.screen.as_str()).block(Block::default().borders(Borders::ALL).title(format!("Screen ({})", status))),
+        screen,
+    );
+
+    let mut lines: Vec<Line> = view
+        .registers
+        .chunks(4)
+        .enumerate()
+        .map(|(row, values)| {
+            let cells: Vec<String> = values
+                .iter()
+                .enumerate()
+                .map(|(col, value)| format!("V{:X}={:02x}", row * 4 + col, value))
+                .collect();
+            Line::from(cells.join(" "))
+        })
+        .collect();
+    lines.push(Line::from(format!("I={:03x} PC={:03x} SP={}", view.address_register, view.program_counter, view.stack_pointer)));
+    lines.push(Line::from(format!("DT={:02x} ST={:02x}", view.delay_timer, view.sound_timer)));
+    for (depth, address) in view.call_stack.iter().enumerate().rev() {
+        lines.push(Line::from(format!("#{} {:03x}", depth, address)));
+    }
+    frame.render_widget(Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title("Registers")), registers);
+
+    let lines: Vec<Line> = view
+        .disassembly
+        .iter()
+        .map(|line| {
+            let marker = match (line.is_pc, line.is_breakpoint) {
+                (true, true) => "*>",
+                (true, false) => " >",
+                (false, true) => "* ",
+                (false, false) => "  ",
+            };
+            let text = format!("{} {:03x}  {:04x}  {}", marker, line.address, line.word, line.text);
+            if line.is_pc {
+                Line::styled(text, Style::default().add_modifier(Modifier::REVERSED))
+            } else {
+                Line::from(text)
+            }
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title("Disassembly")), disassembly);
+
+    let lines: Vec<Line> = view
+        .memory
+        .iter()
+        .map(|(address, bytes)| {
+            let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+            Line::from(format!("{:03x}  {}", address, hex.join(" ")))
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title("Memory")), memory);
+}
+
+fn split<const N: usize>(direction: Direction, area: ratatui::layout::Rect, constraints: [Constraint; N]) -> [ratatui::layout::Rect; N] {
+    let chunks = Layout::default().direction(direction).constraints(constraints).split(area);
+    core::array::from_fn(|idx| chunks[idx])
+}
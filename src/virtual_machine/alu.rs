@@ -0,0 +1,78 @@
+//! The arithmetic behind `8XY4` to `8XYE`. Each helper returns the result and what goes in VF.
+
+
+/// `a + b`, wrapping, with a flag of 1 when it carried.
+pub(crate) fn add_with_carry(a: u8, b: u8) -> (u8, u8) {
+    let (sum, carry) = a.overflowing_add(b);
+    (sum, carry as u8)
+}
+
+/// `a - b`, wrapping. The flag is 1 when there was *no* borrow, as CHIP-8 defines it.
+pub(crate) fn sub_with_borrow(a: u8, b: u8) -> (u8, u8) {
+    let (difference, borrow) = a.overflowing_sub(b);
+    (difference, !borrow as u8)
+}
+
+/// `value >> 1`, with the bit shifted out as the flag.
+pub(crate) fn shr(value: u8) -> (u8, u8) {
+    (value >> 1, value & 0b1)
+}
+
+/// `value << 1`, with the bit shifted out as the flag.
+pub(crate) fn shl(value: u8) -> (u8, u8) {
+    (value << 1, value >> 7)
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    const OPERANDS: [u8; 8] = [0x00, 0x01, 0x0F, 0x10, 0x7F, 0x80, 0xFE, 0xFF];
+
+    #[test]
+    fn add_with_carry_wraps_and_flags() {
+        assert_eq!(add_with_carry(0x00, 0x00), (0x00, 0));
+        assert_eq!(add_with_carry(0xFF, 0x00), (0xFF, 0));
+        assert_eq!(add_with_carry(0xFF, 0x01), (0x00, 1));
+        assert_eq!(add_with_carry(0xFF, 0xFF), (0xFE, 1));
+        assert_eq!(add_with_carry(0x80, 0x7F), (0xFF, 0));
+        for a in OPERANDS {
+            for b in OPERANDS {
+                let wide = a as u16 + b as u16;
+                assert_eq!(add_with_carry(a, b), (wide as u8, (wide > 0xFF) as u8), "{:#04x} + {:#04x}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn sub_with_borrow_wraps_and_flags() {
+        assert_eq!(sub_with_borrow(0x00, 0x00), (0x00, 1));
+        assert_eq!(sub_with_borrow(0x00, 0x01), (0xFF, 0));
+        assert_eq!(sub_with_borrow(0x00, 0xFF), (0x01, 0));
+        assert_eq!(sub_with_borrow(0xFF, 0xFF), (0x00, 1));
+        assert_eq!(sub_with_borrow(0xFF, 0x00), (0xFF, 1));
+        for a in OPERANDS {
+            for b in OPERANDS {
+                let wide = a as i16 - b as i16;
+                assert_eq!(sub_with_borrow(a, b), (wide as u8, (wide >= 0) as u8), "{:#04x} - {:#04x}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn shifts_flag_the_bit_shifted_out() {
+        assert_eq!(shr(0x00), (0x00, 0));
+        assert_eq!(shr(0x01), (0x00, 1));
+        assert_eq!(shr(0xFF), (0x7F, 1));
+        assert_eq!(shr(0x80), (0x40, 0));
+        assert_eq!(shl(0x00), (0x00, 0));
+        assert_eq!(shl(0x80), (0x00, 1));
+        assert_eq!(shl(0xFF), (0xFE, 1));
+        assert_eq!(shl(0x01), (0x02, 0));
+        for value in OPERANDS {
+            assert_eq!(shr(value), (value / 2, value % 2));
+            assert_eq!(shl(value), (((value as u16) << 1) as u8, (value >= 0x80) as u8));
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use alloc::{vec, vec::Vec};
+
+use super::{Renderer, Screen};
+
+
+/// Size of the BMP file header plus the `BITMAPINFOHEADER` that follows it.
+const BMP_HEADER_SIZE: u32 = 14 + 40;
+
+/// The colours lit and unlit pixels are exported with, as RGB.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    pub on: [u8; 3],
+    pub off: [u8; 3],
+}
+
+impl Default for Palette {
+    /// White on black.
+    fn default() -> Self {
+        Self { on: [0xFF; 3], off: [0x00; 3] }
+    }
+}
+
+impl Screen {
+    /// Encodes the screen as an uncompressed 24-bit BMP, with every pixel blown up to a
+    /// `scale` x `scale` square.
+    pub fn to_bmp(&self, scale: u32, palette: &Palette) -> Vec<u8> {
+        let width = self.width() as u32 * scale;
+        let height = self.height() as u32 * scale;
+        // Rows are padded out to a multiple of 4 bytes.
+        let row_size = (3 * width).div_ceil(4) * 4;
+        let file_size = BMP_HEADER_SIZE + row_size * height;
+
+        let mut bytes = Vec::with_capacity(file_size as usize);
+        bytes.extend_from_slice(b"BM");
+        bytes.extend_from_slice(&file_size.to_le_bytes());
+        bytes.extend_from_slice(&[0; 4]);
+        bytes.extend_from_slice(&BMP_HEADER_SIZE.to_le_bytes());
+
+        bytes.extend_from_slice(&40u32.to_le_bytes());
+        bytes.extend_from_slice(&width.to_le_bytes());
+        bytes.extend_from_slice(&height.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes()); // colour planes
+        bytes.extend_from_slice(&24u16.to_le_bytes()); // bits per pixel
+        bytes.extend_from_slice(&0u32.to_le_bytes()); // no compression
+        bytes.extend_from_slice(&(row_size * height).to_le_bytes());
+        bytes.extend_from_slice(&2835u32.to_le_bytes()); // 72 DPI, horizontally
+        bytes.extend_from_slice(&2835u32.to_le_bytes()); // and vertically
+        bytes.extend_from_slice(&[0; 8]); // no colour table
+
+        // Rows are stored bottom-up, and each pixel as BGR.
+        for y in (0..height).rev() {
+            let row_start = bytes.len();
+            for x in 0..width {
+                let index = (x / scale) as usize + (y / scale) as usize * self.width();
+                let [r, g, b] = if self[index] { palette.on } else { palette.off };
+                bytes.extend_from_slice(&[b, g, r]);
+            }
+            bytes.resize(row_start + row_size as usize, 0);
+        }
+        bytes
+    }
+}
+
+/// Renders frames into an RGB image, for frontends that draw pixels themselves.
+///
+/// With `with_fade`, pixels that go dark fade out over a few frames instead, like the phosphor
+/// of a CRT. That hides the flicker of sprites that are erased and redrawn every frame.
+///
+/// The flips and `with_rotate_180` are for displays mounted the wrong way round. They only
+/// change the image; the machine still draws and detects collisions in its own coordinates.
+/// Each is set on its own and they're applied together, so a rotated image that is also
+/// flipped top to bottom ends up mirrored left to right.
+#[derive(Debug, Clone)]
+pub struct ImageRenderer {
+    palette: Palette,
+    fade: f32,
+    flip_horizontal: bool,
+    flip_vertical: bool,
+    rotate_180: bool,
+    width: usize,
+    /// How lit each pixel is, from 0.0 (`palette.off`) to 1.0 (`palette.on`).
+    brightness: Vec<f32>,
+}
+
+impl ImageRenderer {
+    pub fn new(palette: Palette) -> Self {
+        Self { palette, fade: 0.0, flip_horizontal: false, flip_vertical: false, rotate_180: false, width: 0, brightness: Vec::new() }
+    }
+
+    /// Mirror the image left to right.
+    pub fn with_flip_horizontal(mut self, flip: bool) -> Self {
+        self.flip_horizontal = flip;
+        self
+    }
+
+    /// Mirror the image top to bottom.
+    pub fn with_flip_vertical(mut self, flip: bool) -> Self {
+        self.flip_vertical = flip;
+        self
+    }
+
+    /// Turn the image upside down.
+    pub fn with_rotate_180(mut self, rotate: bool) -> Self {
+        self.rotate_180 = rotate;
+        self
+    }
+
+    /// Keep `fade` of a dark pixel's brightness from one frame to the next, from 0.0 (no
+    /// persistence, the default) to 1.0 (never fades). Lit pixels are always fully lit.
+    pub fn with_fade(mut self, fade: f32) -> Self {
+        self.fade = fade.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.brightness.len().checked_div(self.width).unwrap_or(0)
+    }
+
+    /// The colour of the pixel at (`x`, `y`) in the last rendered frame, or `None` if that's
+    /// off the image or nothing has been rendered yet.
+    pub fn pixel(&self, x: usize, y: usize) -> Option<[u8; 3]> {
+        if x >= self.width() || y >= self.height() {
+            return None;
+        }
+        Some(self.colour(self.brightness[x + y * self.width]))
+    }
+
+    /// Every pixel of the last rendered frame as RGB, row by row.
+    pub fn pixels(&self) -> Vec<[u8; 3]> {
+        self.brightness.iter().map(|brightness| self.colour(*brightness)).collect()
+    }
+
+    fn colour(&self, brightness: f32) -> [u8; 3] {
+        let mix = |off: u8, on: u8| (off as f32 + (on as f32 - off as f32) * brightness + 0.5) as u8;
+        let (on, off) = (self.palette.on, self.palette.off);
+        [mix(off[0], on[0]), mix(off[1], on[1]), mix(off[2], on[2])]
+    }
+}
+
+impl Renderer for ImageRenderer {
+    fn render(&mut self, screen: &Screen) {
+        // A resolution change starts over from a dark screen.
+        if screen.width() != self.width || screen.len() != self.brightness.len() {
+            self.width = screen.width();
+            self.brightness = vec![0.0; screen.len()];
+        }
+        let (width, height) = (screen.width(), screen.height());
+        // Turning the image upside down is both flips at once.
+        let flip_horizontal = self.flip_horizontal != self.rotate_180;
+        let flip_vertical = self.flip_vertical != self.rotate_180;
+        for (index, brightness) in self.brightness.iter_mut().enumerate() {
+            let (mut x, mut y) = (index % width, index / width);
+            if flip_horizontal {
+                x = width - 1 - x;
+            }
+            if flip_vertical {
+                y = height - 1 - y;
+            }
+            *brightness = if screen[x + y * width] { 1.0 } else { *brightness * self.fade };
+        }
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn bmp_header_and_pixels() {
+        let mut screen = Screen::default();
+        screen[0] = true;
+        let palette = Palette { on: [0x10, 0x20, 0x30], off: [0; 3] };
+
+        let bmp = screen.to_bmp(3, &palette);
+        let read_u32 = |offset: usize| u32::from_le_bytes(bmp[offset..offset + 4].try_into().unwrap());
+
+        assert_eq!(&bmp[..2], b"BM");
+        assert_eq!(read_u32(2) as usize, bmp.len());
+        assert_eq!((read_u32(18), read_u32(22)), (64 * 3, 32 * 3));
+        assert_eq!(bmp.len() - BMP_HEADER_SIZE as usize, 64 * 3 * 32 * 3 * 3);
+
+        // The top-left pixel is the start of the last row, in BGR.
+        let top_row = bmp.len() - 64 * 3 * 3;
+        assert_eq!(&bmp[top_row..top_row + 9], &[0x30, 0x20, 0x10].repeat(3)[..]);
+        assert_eq!(&bmp[top_row + 9..top_row + 12], &[0, 0, 0]);
+    }
+
+    #[test]
+    fn faded_pixel_is_in_between() {
+        let mut renderer = ImageRenderer::new(Palette::default()).with_fade(0.5);
+        let mut screen = Screen::default();
+        screen[0] = true;
+        screen[1] = true;
+        renderer.render(&screen);
+        assert_eq!(renderer.pixel(0, 0), Some([0xFF; 3]));
+
+        screen[0] = false;
+        renderer.render(&screen);
+        assert_eq!(renderer.pixel(0, 0), Some([0x80; 3]));
+        assert_eq!(renderer.pixel(1, 0), Some([0xFF; 3]));
+        assert_eq!(renderer.pixel(2, 0), Some([0x00; 3]));
+
+        let mut sharp = ImageRenderer::new(Palette::default());
+        assert_eq!(sharp.pixel(0, 0), None);
+        sharp.render(&Screen::default());
+        assert_eq!(sharp.pixel(64, 0), None);
+        assert_eq!(sharp.pixel(0, 32), None);
+        assert_eq!(sharp.pixels(), vec![[0x00; 3]; 64 * 32]);
+    }
+
+    #[test]
+    fn flips_only_move_the_image() {
+        let mut screen = Screen::default();
+        screen[1] = true;
+
+        let mut flipped = ImageRenderer::new(Palette::default()).with_flip_vertical(true).with_flip_vertical(true);
+        flipped.render(&screen);
+        assert_eq!(flipped.pixel(1, 31), Some([0xFF; 3]));
+        assert_eq!(flipped.pixel(1, 0), Some([0x00; 3]));
+
+        let mut rotated = ImageRenderer::new(Palette::default()).with_rotate_180(true);
+        rotated.render(&screen);
+        assert_eq!(rotated.pixel(62, 31), Some([0xFF; 3]));
+        assert!(screen[1]);
+
+        // The order the builders are called in doesn't matter.
+        let mut both = ImageRenderer::new(Palette::default()).with_rotate_180(true).with_flip_vertical(true);
+        let mut other_way = ImageRenderer::new(Palette::default()).with_flip_vertical(true).with_rotate_180(true);
+        both.render(&screen);
+        other_way.render(&screen);
+        assert_eq!(both.pixel(62, 0), Some([0xFF; 3]));
+        assert_eq!(both.pixels(), other_way.pixels());
+
+        let mut undone = ImageRenderer::new(Palette::default()).with_rotate_180(true).with_rotate_180(false);
+        undone.render(&screen);
+        assert_eq!(undone.pixel(1, 0), Some([0xFF; 3]));
+    }
+}
@@ -0,0 +1,166 @@
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+use super::{Memory, Screen, Keypad, Timer};
+
+
+/// Everything the CPU talks to that isn't one of its own registers.
+///
+/// `Chip8` is generic over this so that memory, the display, input and the timers
+/// can be swapped for custom implementations (e.g. a memory-mapped display).
+/// [`Peripherals`] is the default, fully in-memory implementation.
+pub trait Bus {
+    fn read(&self, address: usize) -> u8;
+    fn write(&mut self, address: usize, value: u8);
+
+    fn screen(&self) -> &Screen;
+    fn screen_mut(&mut self) -> &mut Screen;
+
+    fn keypad(&self) -> &Keypad;
+    fn keypad_mut(&mut self) -> &mut Keypad;
+
+    fn delay_timer(&self) -> &Timer;
+    fn delay_timer_mut(&mut self) -> &mut Timer;
+    fn sound_timer(&self) -> &Timer;
+    fn sound_timer_mut(&mut self) -> &mut Timer;
+
+    fn random_byte(&mut self) -> u8;
+
+    fn load(&mut self, offset: usize, bytes: &[u8]) {
+        for (idx, byte) in bytes.iter().enumerate() {
+            self.write(offset + idx, *byte);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Peripherals {
+    pub memory: Memory,
+    pub screen: Screen,
+    pub keypad: Keypad,
+    pub delay_timer: Timer,
+    pub sound_timer: Timer,
+    rng: SmallRng,
+}
+
+impl Default for Peripherals {
+    fn default() -> Self {
+        Self {
+            memory: Memory::default(),
+            screen: Screen::default(),
+            keypad: Keypad::default(),
+            delay_timer: Timer::default(),
+            sound_timer: Timer::default(),
+            rng: default_rng(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+fn default_rng() -> SmallRng {
+    SmallRng::from_entropy()
+}
+
+/// There is no entropy source without `std`, so fall back to a fixed seed.
+#[cfg(not(feature = "std"))]
+fn default_rng() -> SmallRng {
+    SmallRng::seed_from_u64(0)
+}
+
+impl Peripherals {
+    /// Restart the random number generator from `seed`.
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng = SmallRng::seed_from_u64(seed);
+    }
+}
+
+impl Bus for Peripherals {
+    fn read(&self, address: usize) -> u8 {
+        self.memory[address]
+    }
+    fn write(&mut self, address: usize, value: u8) {
+        self.memory[address] = value;
+    }
+
+    fn screen(&self) -> &Screen {
+        &self.screen
+    }
+    fn screen_mut(&mut self) -> &mut Screen {
+        &mut self.screen
+    }
+
+    fn keypad(&self) -> &Keypad {
+        &self.keypad
+    }
+    fn keypad_mut(&mut self) -> &mut Keypad {
+        &mut self.keypad
+    }
+
+    fn delay_timer(&self) -> &Timer {
+        &self.delay_timer
+    }
+    fn delay_timer_mut(&mut self) -> &mut Timer {
+        &mut self.delay_timer
+    }
+    fn sound_timer(&self) -> &Timer {
+        &self.sound_timer
+    }
+    fn sound_timer_mut(&mut self) -> &mut Timer {
+        &mut self.sound_timer
+    }
+
+    fn random_byte(&mut self) -> u8 {
+        self.rng.gen::<u8>()
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::virtual_machine::Chip8;
+
+    /// Delegates to [`Peripherals`] but remembers every memory write.
+    #[derive(Debug, Default)]
+    struct RecordingBus {
+        inner: Peripherals,
+        writes: Vec<(usize, u8)>,
+    }
+
+    impl Bus for RecordingBus {
+        fn read(&self, address: usize) -> u8 { self.inner.read(address) }
+        fn write(&mut self, address: usize, value: u8) {
+            self.writes.push((address, value));
+            self.inner.write(address, value);
+        }
+        fn screen(&self) -> &Screen { self.inner.screen() }
+        fn screen_mut(&mut self) -> &mut Screen { self.inner.screen_mut() }
+        fn keypad(&self) -> &Keypad { self.inner.keypad() }
+        fn keypad_mut(&mut self) -> &mut Keypad { self.inner.keypad_mut() }
+        fn delay_timer(&self) -> &Timer { self.inner.delay_timer() }
+        fn delay_timer_mut(&mut self) -> &mut Timer { self.inner.delay_timer_mut() }
+        fn sound_timer(&self) -> &Timer { self.inner.sound_timer() }
+        fn sound_timer_mut(&mut self) -> &mut Timer { self.inner.sound_timer_mut() }
+        fn random_byte(&mut self) -> u8 { self.inner.random_byte() }
+    }
+
+    #[test]
+    fn fx55_writes_go_through_the_bus() -> Result<(), Box<dyn std::error::Error>> {
+        let mut chip = Chip8::with_bus(RecordingBus::default());
+        chip.initialize();
+        chip.load_program_bytes(&[
+            0x60, 0x11, // V0 = 0x11
+            0x61, 0x22, // V1 = 0x22
+            0x62, 0x33, // V2 = 0x33
+            0xA3, 0x00, // I = 0x300
+            0xF2, 0x55, // store V0..=V2 at I
+        ]);
+        chip.bus_mut().writes.clear();
+
+        for _ in 0..5 {
+            chip.step()?;
+        }
+
+        assert_eq!(chip.bus().writes, vec![(0x300, 0x11), (0x301, 0x22), (0x302, 0x33)]);
+        Ok(())
+    }
+}
@@ -1,33 +1,319 @@
-use core::num;
+use core::fmt;
+use core::num::ParseIntError;
+use core::time::Duration;
+#[cfg(feature = "std")]
 use std::path::Path;
 
-use rand::{rngs::ThreadRng, Rng};
+use alloc::{boxed::Box, collections::BTreeSet, string::String, vec, vec::Vec};
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+#[cfg(feature = "std")]
+use thiserror::Error;
 
-use crate::data_structures::NibblePair;
+#[cfg(feature = "std")]
+use crate::data_structures::HexKeyMap;
 
 use super::{
-    Memory,
     AddressRegister,
     ProgramCounter,
     Stack,
-    StackPointer, Keypad, OpCode, OpLiteral, Timer, Screen, DataRegisters,
-    FontSet
+    StackPointer, OpCode, OpLiteral, DataRegisters,
+    FontSet, FONT_BASE, RegisterError, OpCodeError, Bus, Peripherals, Quirks, Trace, TraceEntry,
+    Renderer, Audio, RegisterSnapshot, InputSource, Clock, TIMER_PERIOD,
+    SaveStateError, OpKind, AddressWidth, DisplayConfig, RomError, MemoryError, MEMORY_SIZE, Screen, ScreenError, FrameDiff, CompatProfile, DrawMode
 };
+use super::rewind::RewindBuffer;
+use super::micro::MicroState;
+use super::tripwire::VfUse;
+use super::random_log::RandomLog;
+use super::alu;
+#[cfg(feature = "std")]
+use super::BundledRoms;
+
+#[derive(Debug)]
+#[cfg_attr(feature = "std", derive(Error))]
+pub enum Chip8Error {
+    #[cfg_attr(feature = "std", error(transparent))]
+    Register(RegisterError),
+    #[cfg_attr(feature = "std", error(transparent))]
+    OpCode(OpCodeError),
+    #[cfg_attr(feature = "std", error(transparent))]
+    Memory(MemoryError),
+    #[cfg_attr(feature = "std", error(transparent))]
+    SaveState(SaveStateError),
+    #[cfg_attr(feature = "std", error(transparent))]
+    Rom(RomError),
+    #[cfg_attr(feature = "std", error(transparent))]
+    Timing(TimingError),
+    #[cfg_attr(feature = "std", error(transparent))]
+    Screen(ScreenError),
+    #[cfg_attr(feature = "std", error("Nothing to step back to: the rewind buffer is empty."))]
+    RewindEmpty,
+    #[cfg_attr(feature = "std", error("Returned from a subroutine with nothing on the call stack."))]
+    StackUnderflow,
+    #[cfg_attr(feature = "std", error("Called a subroutine with all 16 levels of the call stack in use."))]
+    StackOverflow,
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[cfg(feature = "std")]
+    #[error("The CPU thread panicked: {0}")]
+    CpuThreadPanicked(String),
+}
+
+impl From<RegisterError> for Chip8Error {
+    fn from(err: RegisterError) -> Self {
+        Self::Register(err)
+    }
+}
+
+impl From<OpCodeError> for Chip8Error {
+    fn from(err: OpCodeError) -> Self {
+        Self::OpCode(err)
+    }
+}
+
+impl From<MemoryError> for Chip8Error {
+    fn from(err: MemoryError) -> Self {
+        Self::Memory(err)
+    }
+}
+
+impl From<SaveStateError> for Chip8Error {
+    fn from(err: SaveStateError) -> Self {
+        Self::SaveState(err)
+    }
+}
+
+impl From<RomError> for Chip8Error {
+    fn from(err: RomError) -> Self {
+        Self::Rom(err)
+    }
+}
+
+impl From<TimingError> for Chip8Error {
+    fn from(err: TimingError) -> Self {
+        Self::Timing(err)
+    }
+}
+
+impl From<ScreenError> for Chip8Error {
+    fn from(err: ScreenError) -> Self {
+        Self::Screen(err)
+    }
+}
+
+/// A clock setup that can't run, see `Chip8::validate_timing`.
+#[derive(Debug)]
+#[cfg_attr(feature = "std", derive(Error))]
+pub enum TimingError {
+    #[cfg_attr(feature = "std", error("The clock speed is 0 Hz, so no instruction would ever run."))]
+    ZeroClock,
+    #[cfg_attr(feature = "std", error("A {clock_hz} Hz clock at {speed_multiplier}x speed runs less than one instruction per frame."))]
+    NoCyclesPerFrame { clock_hz: u32, speed_multiplier: f32 },
+    #[cfg_attr(feature = "std", error("The timers last ticked at {last_tick:?}, after the clock's current time of {now:?}."))]
+    TimersAheadOfClock { last_tick: Duration, now: Duration },
+}
+
+impl From<ParseIntError> for Chip8Error {
+    fn from(err: ParseIntError) -> Self {
+        Self::Register(err.into())
+    }
+}
+
+/// Why execution handed control back to the caller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// The last instruction changed the screen.
+    Drew,
+    /// The program ran SUPER-CHIP's `00FD` (exit).
+    Halted,
+    /// The next instruction is at a breakpoint and has not run yet.
+    Breakpoint(u16),
+    /// The last instruction changed a watched memory address.
+    Watchpoint { address: u16, old: u8, new: u8 },
+    /// The cycle budget ran out first.
+    CycleLimit,
+    /// The input source asked to quit.
+    UserQuit,
+    /// `run_with_timeout` ran out of time.
+    Timeout,
+    /// The sound timer went from zero to non-zero, see `Chip8::with_beep_stops`.
+    BeepStarted,
+    /// The sound timer ran out, see `Chip8::with_beep_stops`.
+    BeepEnded,
+    /// The next instruction would be fetched from memory nothing ever wrote,
+    /// see `Chip8::with_memory_sanitizer`.
+    UninitializedFetch(u16),
+    /// Nothing was drawn or cleared in the cycles `Chip8::with_display_watchdog` allows.
+    NoDisplayActivity,
+    /// The subroutine `Chip8::step_out` was called in returned.
+    Returned,
+}
+
+/// What `DXYN` does with sprite rows that would be read from past the end of memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SpriteOverrun {
+    /// Draw the rows that are in memory and drop the rest.
+    #[default]
+    Clip,
+    /// Fail with `MemoryError::OutOfBounds` and draw nothing.
+    Error,
+}
+
+/// What the display watchdog does when a program hasn't drawn anything in time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WatchdogAction {
+    /// Log a warning and keep running.
+    #[default]
+    Warn,
+    /// Log a warning and stop with `StopReason::NoDisplayActivity`.
+    Stop,
+}
+
+/// The state behind `Chip8::with_display_watchdog`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct DisplayWatchdog {
+    limit: u64,
+    action: WatchdogAction,
+    fired: bool,
+    /// It fired with `WatchdogAction::Stop` and the stop hasn't been reported yet.
+    stop_pending: bool,
+}
+
+/// What `step` does with a word that isn't an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IllegalOpcodePolicy {
+    /// Fail with `OpCodeError::Unknown`, leaving the program counter on it.
+    #[default]
+    Error,
+    /// Log a warning and move on to the next instruction.
+    Skip,
+}
+
+/// What memory and V0 to VF hold before a program writes them, see `Chip8::with_init_pattern`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InitPattern {
+    /// Zeros, which is what most emulators give you.
+    #[default]
+    Zero,
+    /// Every bit set.
+    Ones,
+    /// Bytes from a generator seeded with this, so runs are still reproducible.
+    Random(u64),
+    /// This byte everywhere.
+    Value(u8),
+}
+
+/// What an instruction did, as seen by a post-exec hook.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepInfo {
+    /// Where the instruction was.
+    pub pc: u16,
+    /// Where execution continues.
+    pub next_pc: u16,
+    /// Instructions executed so far, this one included.
+    pub cycles: u64,
+}
+
+type MachineRoutineFn<B> = dyn FnMut(&mut Chip8<B>, u16) + Send;
+type PreExecHookFn<B> = dyn FnMut(&Chip8<B>, OpCode) + Send;
+type PostExecHookFn<B> = dyn FnMut(&Chip8<B>, OpCode, &StepInfo) + Send;
+type CustomOpcodeFn<B> = dyn FnMut(&mut Chip8<B>, u16) -> Result<(), Chip8Error> + Send;
+
+/// An instruction added with `Chip8::register_opcode`.
+#[derive(Debug)]
+pub(crate) struct CustomOpcode<B> {
+    pattern: u16,
+    mask: u16,
+    handler: Callback<CustomOpcodeFn<B>>,
+}
+
+/// User code installed on a machine, like the `0NNN` handler or the exec hooks.
+pub(crate) struct Callback<F: ?Sized>(Box<F>);
+
+impl<F: ?Sized> fmt::Debug for Callback<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Callback")
+    }
+}
+
+/// The most instructions one frame runs unless `Chip8::with_max_cycles_per_frame` says otherwise,
+/// a thousand times what a 600 Hz machine needs.
+pub const DEFAULT_MAX_CYCLES_PER_FRAME: u32 = 10_000;
+
+#[derive(Debug)]
+pub struct Chip8<B = Peripherals> {
+    pub(crate) data_registers: DataRegisters,
+    pub(crate) address_register: AddressRegister,
+    pub(crate) program_counter: ProgramCounter,
+    pub(crate) stack: Stack,
+    pub(crate) stack_pointer: StackPointer,
+    /// SUPER-CHIP's HP48 "RPL user flags", written by FX75 and read back by FX85.
+    pub(crate) rpl_flags: [u8; 8],
+    pub(crate) should_draw: bool,
+    pub(crate) quirks: Quirks,
+    pub(crate) trace: Option<Trace>,
+    pub(crate) entry_point: u16,
+    /// How many bytes the last `load_program_bytes` loaded, or 0 if the program came from elsewhere.
+    pub(crate) program_len: usize,
+    pub(crate) display: DisplayConfig,
+    pub(crate) clock_hz: u32,
+    pub(crate) speed_multiplier: f32,
+    pub(crate) max_cycles_per_frame: u32,
+    pub(crate) tone_playing: bool,
+    /// Nonzero sound timer values below this are raised to it by FX18.
+    pub(crate) min_beep_ticks: u8,
+    pub(crate) sprite_overrun: SpriteOverrun,
+    pub(crate) init_pattern: InitPattern,
+    pub(crate) illegal_opcode_policy: IllegalOpcodePolicy,
+    /// How many cycles a program gets to draw something, and what happens if it doesn't.
+    pub(crate) display_watchdog: Option<DisplayWatchdog>,
+    pub(crate) beep_stops: bool,
+    pub(crate) paused: bool,
+    pub(crate) halted: bool,
+    pub(crate) breakpoints: BTreeSet<u16>,
+    /// The breakpoint we last stopped at, which is stepped over on the way out.
+    pub(crate) resume_from: Option<u16>,
+    /// Watched addresses along with the value they held last time we looked.
+    pub(crate) watchpoints: Vec<(u16, u8)>,
+    pub(crate) cycles: u64,
+    pub(crate) clock: Option<Box<dyn Clock + Send>>,
+    pub(crate) last_timer_tick: Duration,
+    pub(crate) machine_routine: Option<Callback<MachineRoutineFn<B>>>,
+    pub(crate) pre_exec_hook: Option<Callback<PreExecHookFn<B>>>,
+    pub(crate) post_exec_hook: Option<Callback<PostExecHookFn<B>>>,
+    pub(crate) custom_opcodes: Vec<CustomOpcode<B>>,
+    /// Instructions already decoded, by address. Anything that writes memory outside
+    /// of `step` has to invalidate the entries it overwrote.
+    ///
+    /// A write is seen by the very next fetch, even when it lands on the instruction right after
+    /// the one doing the writing. The only instruction it can't reach is the one already running:
+    /// an FX55 that overwrites itself runs as it was, and the new bytes run the next time round.
+    pub(crate) decode_cache: Vec<Option<OpCode>>,
+    /// Which addresses the font, the program or an instruction has written, when sanitizing.
+    pub(crate) initialized: Option<Vec<bool>>,
+    pub(crate) rewind: Option<RewindBuffer>,
+    /// The deepest the call stack has been since `initialize`.
+    pub(crate) max_stack_depth: usize,
+    /// The highest address an instruction has written since `initialize`.
+    pub(crate) max_memory_write: Option<u16>,
+    /// The key an `FX0A` is waiting to see released, with the `fx0a_on_release` quirk.
+    pub(crate) fx0a_latched: Option<u8>,
+    /// An instruction `micro_step` is partway through.
+    pub(crate) micro: Option<MicroState>,
+    /// What the program last did with VF, if `with_vf_tripwire` is on.
+    pub(crate) vf_tripwire: Option<VfUse>,
+    /// Random bytes being recorded or replayed, see `with_random_recording`.
+    pub(crate) random_log: Option<RandomLog>,
+    /// Which instructions have run since `initialize`, by `OpLiteral`.
+    pub(crate) executed: [bool; OpLiteral::COUNT],
+    pub(crate) bus: B,
+}
 
-#[derive(Debug, Default)]
-pub struct Chip8 {
-    memory: Memory,
-    data_registers: DataRegisters,
-    address_register: AddressRegister,
-    program_counter: ProgramCounter,
-    stack: Stack,
-    stack_pointer: StackPointer,
-    should_draw: bool,
-    keypad: Keypad,
-    delay_timer: Timer,
-    sound_timer: Timer,
-    screen: Screen,
-    rng: ThreadRng,
+impl Default for Chip8 {
+    fn default() -> Self {
+        Self::with_bus(Peripherals::default())
+    }
 }
 
 
@@ -41,30 +327,649 @@ impl Chip8 {
         Self::default()
     }
 
+    /// Seed `CXNN`'s random numbers, so that runs of the same program are reproducible.
+    pub fn with_rng_seed(mut self, seed: u64) -> Self {
+        self.bus.seed_rng(seed);
+        self
+    }
+
+    /// A machine with the font and the ROM at `path` loaded, ready to run from the entry point.
+    /// Falls back to the bundled ROM of that name if there is no such file.
+    #[cfg(feature = "std")]
+    pub fn from_rom<P: AsRef<Path>>(path: P) -> Result<Self, Chip8Error> {
+        let path = path.as_ref();
+        match std::fs::read(path) {
+            Ok(program) => Self::from_rom_bytes(&program),
+            Err(err) => Self::from_rom_bytes(path.to_str().and_then(BundledRoms::get).ok_or(err)?),
+        }
+    }
+
+    /// Like `from_rom`, with the ROM already in memory. ROMs with a built-in `CompatProfile`
+    /// get its quirks and clock speed.
+    pub fn from_rom_bytes(program: &[u8]) -> Result<Self, Chip8Error> {
+        Self::validate_rom(program)?;
+        let mut chip = Self::new();
+        if let Some(profile) = CompatProfile::for_rom(program) {
+            chip.apply_profile(profile);
+        }
+        chip.initialize();
+        chip.load_program_bytes(program);
+        log::info!("Loaded a {} byte ROM with SHA-1 {}.", program.len(), chip.rom_sha1());
+        Ok(chip)
+    }
+
+    /// Loads `program`, runs it for `cycles` instructions with its random numbers seeded from
+    /// `seed`, and returns the final screen as `Screen::to_ascii` text with `#` for lit pixels
+    /// and `.` for unlit ones. The same arguments always give the same frame.
+    pub fn headless_render(program: &[u8], cycles: usize, seed: u64) -> Result<String, Chip8Error> {
+        let mut chip = Self::from_rom_bytes(program)?.with_rng_seed(seed);
+        chip.run_cycles(cycles)?;
+        Ok(chip.bus.screen().to_ascii('#', '.'))
+    }
+}
+
+impl<B: Bus> Chip8<B> {
+    pub fn with_bus(bus: B) -> Self {
+        Self {
+            data_registers: DataRegisters::default(),
+            address_register: AddressRegister::default(),
+            program_counter: ProgramCounter::default(),
+            stack: Stack::default(),
+            stack_pointer: StackPointer::default(),
+            rpl_flags: [0; 8],
+            should_draw: false,
+            quirks: Quirks::default(),
+            trace: None,
+            entry_point: 0x200,
+            program_len: 0,
+            display: DisplayConfig::default(),
+            clock_hz: 600,
+            speed_multiplier: 1.0,
+            max_cycles_per_frame: DEFAULT_MAX_CYCLES_PER_FRAME,
+            tone_playing: false,
+            min_beep_ticks: 0,
+            sprite_overrun: SpriteOverrun::default(),
+            init_pattern: InitPattern::default(),
+            illegal_opcode_policy: IllegalOpcodePolicy::default(),
+            display_watchdog: None,
+            beep_stops: false,
+            paused: false,
+            halted: false,
+            breakpoints: BTreeSet::new(),
+            resume_from: None,
+            watchpoints: Vec::new(),
+            cycles: 0,
+            clock: None,
+            last_timer_tick: Duration::ZERO,
+            machine_routine: None,
+            pre_exec_hook: None,
+            post_exec_hook: None,
+            custom_opcodes: Vec::new(),
+            decode_cache: vec![None; MEMORY_SIZE],
+            initialized: None,
+            rewind: None,
+            max_stack_depth: 0,
+            max_memory_write: None,
+            fx0a_latched: None,
+            micro: None,
+            vf_tripwire: None,
+            random_log: None,
+            executed: [false; OpLiteral::COUNT],
+            bus,
+        }
+    }
+
+    pub fn with_quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = quirks;
+        self
+    }
+
+    /// Where programs are loaded and execution starts. Defaults to 0x200; the ETI-660 uses 0x600.
+    pub fn with_entry_point(mut self, entry_point: u16) -> Self {
+        self.entry_point = entry_point;
+        self
+    }
+
+    /// Stop with `StopReason::UninitializedFetch` instead of executing an instruction from memory
+    /// that neither the font, the loaded program nor an instruction ever wrote. Catches runaway
+    /// program counters.
+    pub fn with_memory_sanitizer(mut self) -> Self {
+        self.initialized = Some(vec![false; MEMORY_SIZE]);
+        self
+    }
+
+    /// How many bits of I are kept: 12 by default, 16 for XO-CHIP. The default bus only has 4K of
+    /// memory, so a 16-bit I is only useful with a bus that has more.
+    pub fn with_address_width(mut self, width: AddressWidth) -> Self {
+        self.address_register = AddressRegister::with_width(width);
+        self
+    }
+
+    /// Use a display other than the usual 64x32, e.g. the ETI-660's 64x48. Clears the screen, and
+    /// keeps writing to a buffer given to `Screen::with_buffer`.
+    /// Each side must be 1 to 255 pixels, and there must be a whole number of bytes' worth of
+    /// pixels, or savestates couldn't hold the screen.
+    pub fn with_display(mut self, display: DisplayConfig) -> Result<Self, Chip8Error> {
+        let side = 1..=255;
+        let packs = (display.width * display.height).is_multiple_of(8);
+        if !side.contains(&display.width) || !side.contains(&display.height) || !packs {
+            return Err(ScreenError::UnsupportedSize { width: display.width, height: display.height }.into());
+        }
+        self.display = display;
+        self.bus.screen_mut().resize(display.width, display.height);
+        Ok(self)
+    }
+
+    pub fn display(&self) -> DisplayConfig {
+        self.display
+    }
+
+    pub fn entry_point(&self) -> u16 {
+        self.entry_point
+    }
+
+    /// Instructions executed per second. Timers always run at 60 Hz.
+    pub fn with_clock_hz(mut self, clock_hz: u32) -> Self {
+        self.clock_hz = clock_hz;
+        self
+    }
+
+    pub fn clock_hz(&self) -> u32 {
+        self.clock_hz
+    }
+
+    /// Instructions executed per frame, after applying the speed multiplier.
+    /// Derived from `clock_hz` alone, so frames cost the same however the host paces them.
+    pub fn cycles_per_frame(&self) -> u32 {
+        (self.clock_hz as f32 * self.speed_multiplier / 60.0) as u32
+    }
+
+    /// Checks that frames will actually run instructions and tick the timers: the clock speed
+    /// isn't 0, at least one instruction fits in a frame after the speed multiplier, and the
+    /// timers haven't been synced to a time the installed clock hasn't reached yet.
+    /// Frames check this before running.
+    pub fn validate_timing(&self) -> Result<(), Chip8Error> {
+        if self.clock_hz == 0 {
+            return Err(TimingError::ZeroClock.into());
+        }
+        if self.cycles_per_frame() == 0 {
+            return Err(TimingError::NoCyclesPerFrame { clock_hz: self.clock_hz, speed_multiplier: self.speed_multiplier }.into());
+        }
+        if let Some(clock) = &self.clock {
+            let now = clock.now();
+            if self.last_timer_tick > now {
+                return Err(TimingError::TimersAheadOfClock { last_tick: self.last_timer_tick, now }.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Never run more than `cycles` instructions in one frame, whatever the clock speed and speed
+    /// multiplier add up to. A frame that hits the cap ends with `StopReason::CycleLimit`, so a
+    /// runaway ROM at a silly speed can't keep the host from rendering.
+    pub fn with_max_cycles_per_frame(mut self, cycles: u32) -> Self {
+        self.max_cycles_per_frame = cycles;
+        self
+    }
+
+    /// Make every beep last at least `ticks` 60 Hz ticks, since a sound timer of 1 or 2 is just a
+    /// click on most audio backends. Programs can't read the sound timer, so this is inaudible to
+    /// game logic. 0, the default, leaves FX18 alone.
+    pub fn with_min_beep_ticks(mut self, ticks: u8) -> Self {
+        self.min_beep_ticks = ticks;
+        self
+    }
+
+    /// Choose what `DXYN` does when I is so close to the top of memory that the sprite doesn't
+    /// fit below it. Well-behaved ROMs never do this; clipping, the default, keeps malformed ones
+    /// running.
+    pub fn with_sprite_overrun(mut self, sprite_overrun: SpriteOverrun) -> Self {
+        self.sprite_overrun = sprite_overrun;
+        self
+    }
+
+    /// Fill memory and V0 to VF with `pattern` instead of leaving them zeroed, since real
+    /// hardware never promised zeros. Programs that read memory or registers before writing them
+    /// then behave differently, which is how those bugs show up. The font and a program that's
+    /// already loaded are left alone, and `initialize` fills everything again.
+    pub fn with_init_pattern(mut self, pattern: InitPattern) -> Self {
+        self.init_pattern = pattern;
+        self.fill_with_init_pattern();
+        self
+    }
+
+    /// Fill memory outside the font and the program, and the V registers, with `init_pattern`.
+    fn fill_with_init_pattern(&mut self) {
+        let mut rng = match self.init_pattern {
+            InitPattern::Random(seed) => Some(SmallRng::seed_from_u64(seed)),
+            _ => None,
+        };
+        let pattern = self.init_pattern;
+        let mut next_byte = move || match pattern {
+            InitPattern::Zero => 0x00,
+            InitPattern::Ones => 0xFF,
+            InitPattern::Value(value) => value,
+            InitPattern::Random(_) => rng.as_mut().map_or(0, |rng| rng.gen::<u8>()),
+        };
+
+        let font = FONT_BASE as usize..FONT_BASE as usize + FontSet::default().0.len();
+        let program = self.entry_point as usize..self.entry_point as usize + self.program_len;
+        for address in 0..MEMORY_SIZE {
+            if !font.contains(&address) && !program.contains(&address) {
+                self.bus.write(address, next_byte());
+            }
+        }
+        for register in 0..16 {
+            self.data_registers.write_idx(register, next_byte()).unwrap();
+        }
+        self.invalidate_decode_cache();
+    }
+
+    /// Stop with `StopReason::BeepStarted` and `StopReason::BeepEnded` whenever the sound timer
+    /// starts or stops the tone, e.g. to check how long a beep lasts against a `MockClock`.
+    pub fn with_beep_stops(mut self) -> Self {
+        self.beep_stops = true;
+        self
+    }
+
+    /// Choose what happens when the program runs into a word that isn't an instruction, usually
+    /// because it jumped into data. Failing, the default, stops a broken ROM where it broke.
+    pub fn with_illegal_opcode_policy(mut self, policy: IllegalOpcodePolicy) -> Self {
+        self.illegal_opcode_policy = policy;
+        self
+    }
+
+    /// Complain if the program hasn't run a `DXYN` or `00E0` by the time it's run `cycles`
+    /// instructions, which usually means it's stuck because of the wrong quirks or clock speed.
+    /// Fires once, as soon as an instruction runs with the limit reached, even if that was
+    /// before the watchdog was set up. The stop is reported by the next run that can report it.
+    pub fn with_display_watchdog(mut self, cycles: u64, action: WatchdogAction) -> Self {
+        self.display_watchdog = Some(DisplayWatchdog { limit: cycles, action, fired: false, stop_pending: false });
+        self
+    }
+
+    /// Called after every instruction.
+    fn check_display_watchdog(&mut self) {
+        let drew = self.executed[OpLiteral::_DXYN as usize] || self.executed[OpLiteral::_00E0 as usize];
+        let Some(watchdog) = &mut self.display_watchdog else {
+            return;
+        };
+        if !watchdog.fired && !drew && self.cycles >= watchdog.limit {
+            log::warn!(
+                "Nothing has been drawn in {} cycles. The ROM may need different quirks or a different clock speed.",
+                watchdog.limit
+            );
+            watchdog.fired = true;
+            watchdog.stop_pending = watchdog.action == WatchdogAction::Stop;
+        }
+    }
+
+    /// Whether there's a watchdog stop to report, forgetting it if so.
+    fn take_watchdog_stop(&mut self) -> bool {
+        self.display_watchdog.as_mut().is_some_and(|watchdog| core::mem::take(&mut watchdog.stop_pending))
+    }
+
+    /// Scale how many instructions run per frame (2.0 is double speed, 0.25 slow motion).
+    /// Timers keep ticking at 60 Hz regardless.
+    pub fn set_speed_multiplier(&mut self, multiplier: f32) {
+        self.speed_multiplier = multiplier;
+    }
+
+    pub fn speed_multiplier(&self) -> f32 {
+        self.speed_multiplier
+    }
+
+    /// Total number of instructions executed so far.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// The deepest the call stack has been since `initialize`, out of 16.
+    pub fn max_stack_depth(&self) -> usize {
+        self.max_stack_depth
+    }
+
+    /// The highest address `FX33` or `FX55` has written since `initialize`, if any.
+    pub fn max_memory_write(&self) -> Option<u16> {
+        self.max_memory_write
+    }
+
+    /// The delay timer as of its last tick. With a clock installed, call `sync_timers` first to
+    /// apply the ticks that are due.
+    pub fn delay_timer(&self) -> u8 {
+        self.bus.delay_timer().value()
+    }
+
+    /// The sound timer as of its last tick, see `delay_timer`.
+    pub fn sound_timer(&self) -> u8 {
+        self.bus.sound_timer().value()
+    }
+
+    /// Stop executing instructions. `run_frame` becomes a no-op until `resume`.
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Drive the delay and sound timers from `clock` instead of once per `run_frame`.
+    /// Timer reads and writes (`FX07`/`FX15`/`FX18`) then see exactly the number of
+    /// 60 Hz ticks that elapsed on the clock.
+    pub fn with_clock<C: Clock + Send + 'static>(mut self, clock: C) -> Self {
+        self.last_timer_tick = clock.now();
+        self.clock = Some(Box::new(clock));
+        self
+    }
+
+    /// Stop before executing the instruction at `address`.
+    pub fn add_breakpoint(&mut self, address: u16) {
+        self.breakpoints.insert(address);
+    }
+
+    pub fn remove_breakpoint(&mut self, address: u16) {
+        self.breakpoints.remove(&address);
+    }
+
+    pub fn has_breakpoint(&self, address: u16) -> bool {
+        self.breakpoints.contains(&address)
+    }
+
+    /// Stop after any instruction that changes the byte at `address`.
+    pub fn add_watchpoint(&mut self, address: u16) {
+        let value = self.bus.read(address as usize);
+        self.watchpoints.retain(|(watched, _)| *watched != address);
+        self.watchpoints.push((address, value));
+    }
+
+    pub fn remove_watchpoint(&mut self, address: u16) {
+        self.watchpoints.retain(|(watched, _)| *watched != address);
+    }
+
+    /// Whether the screen changed since the last call, for hosts that render on their own
+    /// instead of through `run_frame`. Clears the flag.
+    pub fn take_redraw(&mut self) -> bool {
+        let utilities = self.bus.screen_mut().take_dirty();
+        core::mem::take(&mut self.should_draw) | utilities
+    }
+
+    /// Whether the program exited with `00FD`. Cleared by `initialize`.
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Run `handler` with the address NNN whenever a `0NNN` machine code call executes.
+    /// The program counter has already moved past the instruction when it is called.
+    pub fn set_machine_routine_handler<F: FnMut(&mut Chip8<B>, u16) + Send + 'static>(&mut self, handler: F) {
+        self.machine_routine = Some(Callback(Box::new(handler)));
+    }
+
+    /// Claim every word where `word & mask == pattern` for `handler`, which then runs instead of
+    /// the built-in instruction, if any. It gets the whole word and has to move the program
+    /// counter itself. Earlier registrations win when patterns overlap. Custom instructions
+    /// aren't seen by the exec hooks or the trace.
+    pub fn register_opcode<F>(&mut self, pattern: u16, mask: u16, handler: F)
+    where
+        F: FnMut(&mut Chip8<B>, u16) -> Result<(), Chip8Error> + Send + 'static,
+    {
+        self.custom_opcodes.push(CustomOpcode { pattern, mask, handler: Callback(Box::new(handler)) });
+    }
+
+    /// Which custom instruction claims the word at the program counter, if any, and the word.
+    fn find_custom_opcode(&self) -> Option<(usize, u16)> {
+        if self.custom_opcodes.is_empty() {
+            return None;
+        }
+        let word = self.fetch_opcode();
+        self.custom_opcodes
+            .iter()
+            .position(|custom| word & custom.mask == custom.pattern)
+            .map(|idx| (idx, word))
+    }
+
+    fn run_custom_opcode(&mut self, idx: usize, word: u16) -> Result<(), Chip8Error> {
+        // Taken out for the call, like the hooks, since the handler borrows the whole machine.
+        let mut customs = core::mem::take(&mut self.custom_opcodes);
+        let result = (customs[idx].handler.0)(self, word);
+        customs.append(&mut self.custom_opcodes);
+        self.custom_opcodes = customs;
+        result?;
+        self.cycles += 1;
+        Ok(())
+    }
+
+    /// Run `hook` with each instruction just before it executes.
+    pub fn set_pre_exec_hook<F: FnMut(&Chip8<B>, OpCode) + Send + 'static>(&mut self, hook: F) {
+        self.pre_exec_hook = Some(Callback(Box::new(hook)));
+    }
+
+    /// Run `hook` with each instruction just after it executed.
+    pub fn set_post_exec_hook<F: FnMut(&Chip8<B>, OpCode, &StepInfo) + Send + 'static>(&mut self, hook: F) {
+        self.post_exec_hook = Some(Callback(Box::new(hook)));
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    pub fn register_snapshot(&self) -> RegisterSnapshot {
+        RegisterSnapshot {
+            data_registers: self.data_registers,
+            address_register: self.address_register.read(),
+            program_counter: self.program_counter.read(),
+            stack_pointer: self.stack_pointer,
+        }
+    }
+
+    /// The pixels that turned on and off since `prev`, typically a clone of the last rendered screen.
+    pub fn frame_diff(&self, prev: &Screen) -> FrameDiff {
+        self.bus.screen().diff(prev)
+    }
+
+    /// Return addresses of the active subroutine calls, outermost first.
+    /// Each entry is the address of the `2NNN` that made the call.
+    pub fn call_stack(&self) -> &[u16] {
+        &self.stack[..self.stack_pointer as usize]
+    }
+
+    /// All 16 stack slots, including the ones past the stack pointer.
+    pub fn stack_slice(&self) -> &[u16] {
+        &self.stack
+    }
+
+    /// Replace the stack with `stack`, clearing any slots it doesn't cover, and point the stack
+    /// pointer at `sp`. Rejects more than 16 entries or an `sp` past the end of the stack,
+    /// leaving the machine untouched.
+    pub fn set_stack(&mut self, stack: &[u16], sp: u16) -> Result<(), Chip8Error> {
+        if sp as usize > self.stack.len() || stack.len() > self.stack.len() {
+            return Err(SaveStateError::StackPointerOutOfBounds(sp.max(stack.len() as u16)).into());
+        }
+        self.stack = [0; 16];
+        self.stack[..stack.len()].copy_from_slice(stack);
+        self.stack_pointer = sp;
+        Ok(())
+    }
+
+    /// Start recording the last `capacity` executed instructions.
+    pub fn enable_trace(&mut self, capacity: usize) {
+        self.trace = Some(Trace::with_capacity(capacity));
+    }
+
+    /// Only trace opcodes of the given kinds. Has no effect unless tracing is enabled.
+    pub fn trace_only(&mut self, kinds: &[OpKind]) {
+        if let Some(trace) = &mut self.trace {
+            trace.only(kinds);
+        }
+    }
+
+    pub fn disable_trace(&mut self) {
+        self.trace = None;
+    }
+
+    pub fn trace(&self) -> Option<&Trace> {
+        self.trace.as_ref()
+    }
+
+    /// Writes the recorded trace as JSON lines, oldest instruction first.
+    /// Writes nothing if tracing is disabled.
+    #[cfg(feature = "std")]
+    pub fn trace_to_writer<W: std::io::Write>(&self, mut writer: W) -> std::io::Result<()> {
+        if let Some(trace) = &self.trace {
+            for entry in trace.iter() {
+                entry.write_json(&mut writer)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn bus(&self) -> &B {
+        &self.bus
+    }
+
+    /// Anything may be written through the returned bus, so this forgets all decoded instructions.
+    pub fn bus_mut(&mut self) -> &mut B {
+        self.invalidate_decode_cache();
+        &mut self.bus
+    }
+
     pub fn initialize(&mut self) {
 
         // Set program counter.
-        self.program_counter.write(0x200).unwrap();
+        self.program_counter.write(self.entry_point).unwrap();
+        self.halted = false;
+        self.micro = None;
+        self.fx0a_latched = None;
+        if self.vf_tripwire.is_some() {
+            self.vf_tripwire = Some(VfUse::Untouched);
+        }
+        self.max_stack_depth = 0;
+        self.max_memory_write = None;
+        self.executed = [false; OpLiteral::COUNT];
+        if let Some(watchdog) = &mut self.display_watchdog {
+            (watchdog.fired, watchdog.stop_pending) = (false, false);
+        }
+        if self.init_pattern != InitPattern::Zero {
+            self.fill_with_init_pattern();
+        }
 
         // Load fontset.
-        self.memory.load_font_data(&FontSet::default(), 0);
+        let font = FontSet::default().0;
+        self.bus.load(FONT_BASE as usize, &font);
+        self.invalidate_decode_cache();
+        self.mark_initialized(FONT_BASE as usize..FONT_BASE as usize + font.len());
+    }
+
+    /// Writes a byte straight into memory, outside of the normal instruction flow.
+    pub fn poke(&mut self, address: u16, value: u8) -> Result<(), Chip8Error> {
+        let address = address as usize;
+        if address >= MEMORY_SIZE {
+            return Err(MemoryError::OutOfBounds(address).into());
+        }
+        self.bus.write(address, value);
+        self.invalidate_decoded(address..address + 1);
+        self.mark_initialized(address..address + 1);
+        Ok(())
+    }
+
+    /// Sets V0 to VF directly, outside of the normal instruction flow.
+    pub fn set_register(&mut self, register: u8, value: u8) -> Result<(), Chip8Error> {
+        self.data_registers.write_idx(register as usize, value)?;
+        Ok(())
+    }
+
+    /// Forget the decoded instructions overlapping `addresses`.
+    /// An instruction starting one byte before the range overlaps it too.
+    pub(crate) fn invalidate_decoded(&mut self, addresses: core::ops::Range<usize>) {
+        let start = addresses.start.saturating_sub(1);
+        let end = addresses.end.min(MEMORY_SIZE);
+        for entry in &mut self.decode_cache[start.min(end)..end] {
+            *entry = None;
+        }
+    }
+
+    pub(crate) fn invalidate_decode_cache(&mut self) {
+        self.decode_cache.fill(None);
+    }
+
+    /// Bookkeeping for an instruction that wrote `addresses`.
+    fn wrote_memory(&mut self, addresses: core::ops::Range<usize>) {
+        self.invalidate_decoded(addresses.clone());
+        self.mark_initialized(addresses.clone());
+        if let Some(last) = addresses.end.checked_sub(1) {
+            let last = last.min(MEMORY_SIZE - 1) as u16;
+            self.max_memory_write = Some(self.max_memory_write.map_or(last, |max| max.max(last)));
+        }
+    }
+
+    /// Record that `addresses` have been written, for the memory sanitizer.
+    pub(crate) fn mark_initialized(&mut self, addresses: core::ops::Range<usize>) {
+        if let Some(initialized) = &mut self.initialized {
+            let end = addresses.end.min(MEMORY_SIZE);
+            initialized[addresses.start.min(end)..end].fill(true);
+        }
+    }
+
+    fn is_initialized(&self, address: usize) -> bool {
+        self.initialized.as_ref().is_none_or(|initialized| initialized.get(address).copied().unwrap_or(false))
     }
 
+    /// Loads the program at `path`, falling back to the bundled ROM of that name
+    /// if there is no such file.
+    #[cfg(feature = "std")]
     pub fn load_program<P: AsRef<Path>>(&mut self, path: P) {
-        let program_offset = 512usize;
-        if let Ok(program) = std::fs::read(path) {
-            for (idx, item) in program.iter().enumerate() {
-                self.memory[program_offset + idx] = *item;
-            }
+        if let Ok(program) = std::fs::read(&path) {
+            self.load_program_bytes(&program);
+        } else if let Some(program) = path.as_ref().to_str().and_then(BundledRoms::get) {
+            self.load_program_bytes(program);
         }
     }
 
-    fn fetch_opcode(&self) -> u16 {
-        let current_pc = self.program_counter.read() as usize;
-        ((self.memory[current_pc] as usize) << 8) as u16 | (self.memory[current_pc + 1] as u16)
+    /// Copies `program` into memory at the entry point. Bytes that would land past the end of
+    /// memory are dropped with a warning; `load_program_bytes_validated` rejects such ROMs instead.
+    pub fn load_program_bytes(&mut self, program: &[u8]) {
+        let program_offset = self.entry_point as usize;
+        let fits = MEMORY_SIZE.saturating_sub(program_offset);
+        let program = if program.len() > fits {
+            log::warn!(
+                "Truncated a {} byte ROM to the {} bytes between {:#05x} and the end of memory.",
+                program.len(), fits, program_offset,
+            );
+            &program[..fits]
+        } else {
+            program
+        };
+        self.bus.load(program_offset, program);
+        self.invalidate_decoded(program_offset..program_offset + program.len());
+        self.mark_initialized(program_offset..program_offset + program.len());
+        self.program_len = program.len();
+    }
+
+    /// The word at the program counter. One at 0xFFF takes its low byte from 0x000.
+    pub(crate) fn fetch_opcode(&self) -> u16 {
+        let current_pc = self.program_counter.read() as usize % MEMORY_SIZE;
+        let next = (current_pc + 1) % MEMORY_SIZE;
+        ((self.bus.read(current_pc) as usize) << 8) as u16 | (self.bus.read(next) as u16)
+    }
+
+    /// Fails unless the `count` bytes starting at I are all in memory.
+    fn check_index_range(&self, count: usize) -> Result<(), Chip8Error> {
+        let end = self.address_register.read() as usize + count;
+        if end > MEMORY_SIZE {
+            return Err(MemoryError::OutOfBounds(end - 1).into());
+        }
+        Ok(())
     }
 
-    fn apply_opcode(&mut self, opcode: u16) -> Result<(), Box<dyn std::error::Error>> {
+    fn apply_opcode(&mut self, opcode: u16) -> Result<(), Chip8Error> {
         let maybe_opcode = OpCode::try_from(opcode);
         if maybe_opcode.is_err() {
             unreachable!("Only known opcodes may be applied.");
@@ -74,16 +979,56 @@ impl Chip8 {
 
         match opcode.literal {
             OpLiteral::_0NNN => {
-                // Call machine code routine (RCA 1802 for COSMAC VIP) at address NNN. Not necessary for most ROMs.
+                // Call machine code routine (RCA 1802 for COSMAC VIP) at address NNN. Not necessary for most ROMs,
+                // so this is skipped unless a handler was installed.
+                self.program_counter.step(2)?;
+
+                if let Some(mut routine) = self.machine_routine.take() {
+                    (routine.0)(self, opcode.nnn());
+                    self.machine_routine.get_or_insert(routine);
+                }
             },
             OpLiteral::_00E0 => {
-                // Clear the screen.
-                self.screen.clear();
+                // Clear the screen. Flags a redraw even if it was already clear.
+                self.bus.screen_mut().clear();
+                self.should_draw = true;
+                self.program_counter.step(2)?;
+            },
+            OpLiteral::_00CN => {
+                // Scroll the screen down N rows (SUPER-CHIP).
+                self.bus.screen_mut().scroll_down(opcode.n() as usize);
+                self.should_draw = true;
+                self.program_counter.step(2)?;
+            },
+            OpLiteral::_00DN => {
+                // Scroll the screen up N rows (XO-CHIP).
+                self.bus.screen_mut().scroll_up(opcode.n() as usize);
+                self.should_draw = true;
+                self.program_counter.step(2)?;
+            },
+            OpLiteral::_00FD => {
+                // Exit the interpreter (SUPER-CHIP). The program counter stays put.
+                self.halted = true;
+            },
+            OpLiteral::_00FE => {
+                // Switch back to the low resolution display (SUPER-CHIP), 64x32 unless configured
+                // otherwise. Clears the screen.
+                self.bus.screen_mut().resize(self.display.width, self.display.height);
+                self.should_draw = true;
+                self.program_counter.step(2)?;
+            },
+            OpLiteral::_00FF => {
+                // Switch to the 128x64 display (SUPER-CHIP). Clears the screen.
+                self.bus.screen_mut().set_hires(true);
+                self.should_draw = true;
                 self.program_counter.step(2)?;
             },
             OpLiteral::_00EE => {
                 // Returns from a subroutine.
 
+                if self.stack_pointer == 0 {
+                    return Err(Chip8Error::StackUnderflow);
+                }
                 self.stack_pointer -= 1;
                 let previous_program_counter = self.stack[self.stack_pointer as usize];
                 
@@ -98,454 +1043,2120 @@ impl Chip8 {
             },
             OpLiteral::_1NNN => {
                 // Jumps to address NNN.
-                self.program_counter.write(opcode.value & 0x0FFF)?;
+                self.program_counter.write(opcode.nnn())?;
 
             }
             OpLiteral::_2NNN => {
                 // Calls subroutine at NNN.
 
                 // Save current program counter.
+                if self.stack_pointer as usize == self.stack.len() {
+                    return Err(Chip8Error::StackOverflow);
+                }
                 self.stack[self.stack_pointer as usize] = self.program_counter.read();
                 self.stack_pointer += 1;
+                self.max_stack_depth = self.max_stack_depth.max(self.stack_pointer as usize);
+                if self.stack_pointer as usize == self.stack.len() {
+                    log::warn!("The call stack is full after a call to {:#05x}; one more call overflows it.", opcode.nnn());
+                }
 
                 // Move program counter to the subroutine's address.
-                self.program_counter.write(opcode.value & 0x0FFF)?;
+                self.program_counter.write(opcode.nnn())?;
 
             },
             OpLiteral::_3XNN => {
                 // Skips the next instruction if VX equals NN (usually the next instruction is a jump to skip a code block.)
-                let register_identifier = ((opcode.value & 0x0F00) >> 8) as u8;
-                let quad: NibblePair = register_identifier.into();
-                let register_identifier = quad.high.to_hex_char();
-                let data = (opcode.value & 0x00FF) as u8;
-                
+                let data_x = self.data_registers.read_idx(opcode.x() as usize)?;
+
                 self.program_counter.step(2)?;
 
-                if let Ok(value_in_register) = self.data_registers.read(register_identifier) {
-                    if value_in_register == data {
-                        // Skip next instruction if (Vx == NN).
-                        self.program_counter.step(2)?;
-                    }
-                }
-            },
-            OpLiteral::_4XNN => {
-                // Skips the next instruction if VX equals NN (usually the next instruction is a jump to skip a code block.)
-                let register_identifier = ((opcode.value & 0x0F00) >> 8) as u8;
+                if data_x == opcode.nn() {
+                    // Skip next instruction if (Vx == NN).
+                    self.program_counter.step(2)?;
+                }
+            },
+            OpLiteral::_4XNN => {
+                // Skips the next instruction if VX equals NN (usually the next instruction is a jump to skip a code block.)
+                let data_x = self.data_registers.read_idx(opcode.x() as usize)?;
+
+                self.program_counter.step(2)?;
+
+                if data_x != opcode.nn() {
+                    // Skip next instruction if (Vx != NN).
+                    self.program_counter.step(2)?;
+                }
+            },
+            OpLiteral::_5XY0 => {
+                // Skips the next instruction if VX equals Vy (usually the next instruction is a jump to skip a code block.)
+                let data_x = self.data_registers.read_idx(opcode.x() as usize)?;
+                let data_y = self.data_registers.read_idx(opcode.y() as usize)?;
+                
+                self.program_counter.step(2)?;
+
+                if data_x == data_y {
+                    self.program_counter.step(2)?;
+                }
+            },
+            OpLiteral::_6XNN => {
+                // Sets Vx to NN.
+
+                let nn = opcode.nn();
+                
+                let register_x = opcode.x() as usize;
+
+                self.data_registers.write_idx(register_x, nn)?;
+                self.program_counter.step(2)?;
+            },
+            OpLiteral::_7XNN => {
+                // Adds NN to Vx (carry flag is not changed).
+                let nn = opcode.nn();
+                let register_x = opcode.x() as usize;
+
+                self.data_registers.write_idx(
+                    register_x, 
+                    self.data_registers.read_idx(register_x)?.wrapping_add(nn)
+                )?;
+                self.program_counter.step(2)?;
+            },
+            OpLiteral::_8XY0 => {
+                // Sets Vx to the value of Vy.
+                let register_x = opcode.x() as usize;
+                let register_y = opcode.y() as usize;
+
+                let data_y = self.data_registers.read_idx(register_y)?;
+                self.data_registers.write_idx(register_x, data_y)?;
+                
+                self.program_counter.step(2)?;
+            },
+            OpLiteral::_8XY1 => {
+                // Sets Vx to the value of Vx | Vy.
+                let register_x = opcode.x() as usize;
+                let register_y = opcode.y() as usize;
+
+                let data_y = self.data_registers.read_idx(register_y)?;
+                let data_x = self.data_registers.read_idx(register_x)?;
+
+                self.data_registers.write_idx(register_x, data_y | data_x)?;
+                if self.quirks.logic_vf_reset {
+                    self.data_registers.write_idx(15, 0)?;
+                }
+                
+                self.program_counter.step(2)?;
+            },
+            OpLiteral::_8XY2 => {
+                // Sets Vx to the value of Vx & Vy.
+                let register_x = opcode.x() as usize;
+                let register_y = opcode.y() as usize;
+
+                let data_y = self.data_registers.read_idx(register_y)?;
+                let data_x = self.data_registers.read_idx(register_x)?;
+
+                self.data_registers.write_idx(register_x, data_y & data_x)?;
+                if self.quirks.logic_vf_reset {
+                    self.data_registers.write_idx(15, 0)?;
+                }
+                
+                self.program_counter.step(2)?;
+            },
+            OpLiteral::_8XY3 => {
+                // Sets Vx to the value of Vx ^ Vy.
+                let register_x = opcode.x() as usize;
+                let register_y = opcode.y() as usize;
+
+                let data_y = self.data_registers.read_idx(register_y)?;
+                let data_x = self.data_registers.read_idx(register_x)?;
+
+                self.data_registers.write_idx(register_x, data_y ^ data_x)?;
+                if self.quirks.logic_vf_reset {
+                    self.data_registers.write_idx(15, 0)?;
+                }
+                
+                self.program_counter.step(2)?;
+            },
+            OpLiteral::_8XY4 => {
+
+                // Adds Vy to Vx. Vf is set to 1 when there's a carry, and to 0 when there is not.
+                let register_x = opcode.x() as usize;
+                let register_y = opcode.y() as usize;
+
+                let data_y = self.data_registers.read_idx(register_y)?;
+                let data_x = self.data_registers.read_idx(register_x)?;
+                
+                let (sum, carry) = alu::add_with_carry(data_x, data_y);
+
+                // The flag is written last, so it wins over the sum when X is F.
+                self.data_registers.write_idx(register_x, sum)?;
+                self.data_registers.write_idx(15, carry)?;
+                
+                self.program_counter.step(2)?;
+            },
+            OpLiteral::_8XY5 => {
+
+                // Subtract Vy from Vx. Vf is set to 0 when there's a borrow, and to 1 when there is not.
+
+                let register_x = opcode.x() as usize;
+                let register_y = opcode.y() as usize;
+
+                let data_y = self.data_registers.read_idx(register_y)?;
+                let data_x = self.data_registers.read_idx(register_x)?;
+                
+                let (difference, not_borrow) = alu::sub_with_borrow(data_x, data_y);
+
+                // The flag is written last, so it wins over the difference when X is F.
+                self.data_registers.write_idx(register_x, difference)?;
+                self.data_registers.write_idx(15, not_borrow)?;
+                self.program_counter.step(2)?;
+            },
+            OpLiteral::_8XY6 => {
+                // Stores the least significant bit of Vx in Vf and then shift Vx to the right by 1.
+                // With the `shift_vy` quirk, Vy is shifted instead and the result is stored in Vx.
+
+                let register_x = opcode.x() as usize;
+                let register_y = opcode.y() as usize;
+                let source = if self.quirks.shift_vy { register_y } else { register_x };
+
+                // 1. Read the source once. 2. Take the bit shifted out of that pre-shift value.
+                // 3. Write the shifted value to Vx. 4. Write the bit to Vf, so it wins when X is F.
+                let (shifted, shifted_out) = alu::shr(self.data_registers.read_idx(source)?);
+
+                self.data_registers.write_idx(register_x, shifted)?;
+                self.data_registers.write_idx(15, shifted_out)?;
+
+                self.program_counter.step(2)?;
+            },
+            OpLiteral::_8XY7 => {
+
+                // Subtract Vx from Vy and assign to Vx. Vf is set to 0 when there's a borrow, and to 1 when there is not.
+
+                let register_x = opcode.x() as usize;
+                let register_y = opcode.y() as usize;
+
+                let data_y = self.data_registers.read_idx(register_y)?;
+                let data_x = self.data_registers.read_idx(register_x)?;
+                
+                let (difference, not_borrow) = alu::sub_with_borrow(data_y, data_x);
+
+                // The flag is written last, so it wins over the difference when X is F.
+                self.data_registers.write_idx(register_x, difference)?;
+                self.data_registers.write_idx(15, not_borrow)?;
+                self.program_counter.step(2)?;
+            },
+            OpLiteral::_8XYE => {
+                // Stores the most significant bit of Vx in Vf and then shift Vx to the left by 1.
+                // With the `shift_vy` quirk, Vy is shifted instead and the result is stored in Vx.
+
+                let register_x = opcode.x() as usize;
+                let register_y = opcode.y() as usize;
+                let source = if self.quirks.shift_vy { register_y } else { register_x };
+
+                // Same sequence as 8XY6, with the most significant bit shifted out instead.
+                let (shifted, shifted_out) = alu::shl(self.data_registers.read_idx(source)?);
+
+                self.data_registers.write_idx(register_x, shifted)?;
+                self.data_registers.write_idx(15, shifted_out)?;
+
+                self.program_counter.step(2)?;
+            },
+            OpLiteral::_9XY0 => {
+                // Skip the next instruction if Vx does not equal Vy. (Usually the instruction is a jump to skip a code block)
+
+                let register_x = opcode.x() as usize;
+                let register_y = opcode.y() as usize;
+
+                let data_y = self.data_registers.read_idx(register_y)?;
+                let data_x = self.data_registers.read_idx(register_x)?;
+
+                self.program_counter.step(2)?;
+
+                if data_x != data_y {
+                    self.program_counter.step(2)?;
+                }
+            },
+            OpLiteral::_ANNN => {
+                // Sets the address_register to NNN.
+
+                // Extract the last three quads.
+                let value = opcode.nnn();
+                self.address_register.write(value)?;
+                self.program_counter.step(2)?;
+            },
+            OpLiteral::_BNNN => {
+                // Jumps to the address NNN plus V0.
+                // With the `jump_vx` quirk this is BXNN: jump to XNN plus VX.
+                let register = if self.quirks.jump_vx { opcode.x() as usize } else { 0 };
+                self.program_counter.write((opcode.nnn() + self.data_registers.read_idx(register)? as u16) & 0x0FFF)?;
+            }
+            OpLiteral::_CXNN => {
+                // Sets Vx to the result of a bitwise and operation on a random number (Typically: 0 to 255) and NN.
+                let nn = opcode.nn();
+
+                let register_x = opcode.x() as usize;
+                let random = self.next_random_byte();
+                self.data_registers.write_idx(register_x, nn & random)?;
+                self.program_counter.step(2)?;
+            },
+            OpLiteral::_DXYN => {
+                // Draws a sprite at coordinate (Vx, Vy) that has a width of 8 pixels and height of N pixels. 
+                // Each row of 8 pixels is read as bit-coded starting from memory location I; I value does not change
+                // after the execution of this instruction. Vf is set to 1 if any screen pixels are flipped from set to unset when
+                // the sprite is drawn, and to 0 if that does not happen.
+
+                let register_x = opcode.x() as usize;
+                let register_y = opcode.y() as usize;
+
+                let data_x = self.data_registers.read_idx(register_x)?;
+                let data_y = self.data_registers.read_idx(register_y)?;
+
+                // In SUPER-CHIP's hires mode, DXY0 draws a 16x16 sprite (two bytes per row) instead.
+                // Otherwise it's a sprite with no rows: nothing is drawn, nothing collides and there's
+                // nothing new to show.
+                let (width, num_rows) = match opcode.n() {
+                    0 if self.bus.screen().is_hires() => (16u8, 16u8),
+                    0 => {
+                        self.data_registers.write_idx(15, 0)?;
+                        self.program_counter.step(2)?;
+                        return Ok(());
+                    },
+                    rows => (8u8, rows),
+                };
+                let row_len = width as usize / 8;
+
+                // Rows past the end of memory don't exist, see `SpriteOverrun`.
+                let current_address = self.address_register.read() as usize;
+                let rows_in_memory = MEMORY_SIZE.saturating_sub(current_address) / row_len;
+                let num_rows = if rows_in_memory >= num_rows as usize {
+                    num_rows
+                } else if self.sprite_overrun == SpriteOverrun::Clip {
+                    log::warn!(
+                        "Clipped a {} row sprite at {:#05x} to the {} rows before the end of memory.",
+                        num_rows, current_address, rows_in_memory,
+                    );
+                    rows_in_memory as u8
+                } else {
+                    return Err(MemoryError::OutOfBounds(current_address + num_rows as usize * row_len - 1).into());
+                };
+                let sprite_len = num_rows as usize * row_len;
+
+                let mut sprite = [0u8; 32];
+                for (offset, byte) in sprite[..sprite_len].iter_mut().enumerate() {
+                    *byte = self.bus.read(current_address + offset);
+                }
+
+                // Pixels running off an edge are clipped, unless the `sprite_wrap` quirk is on
+                // and they reappear on the other side.
+                let screen = self.bus.screen_mut();
+                let collision = if self.quirks.sprite_wrap {
+                    screen.draw_sprite_wrapping(data_x, data_y, width, num_rows, &sprite[..sprite_len], DrawMode::Xor)
+                } else {
+                    screen.draw_sprite(data_x, data_y, width, num_rows, &sprite[..sprite_len], DrawMode::Xor)
+                };
+                self.data_registers.write_idx(15, collision as u8)?;
+
+                self.should_draw = true;
+                self.program_counter.step(2)?;
+            },
+            OpLiteral::_EX9E => {
+                // Skips the next instruction if the key stored in Vx is pressed (usually the next instruction is a jump to skip a code block).
+
+                let register_x = opcode.x() as usize;
+                let data_x = self.data_registers.read_idx(register_x)?;
+
+                self.program_counter.step(2)?;
+
+                if self.bus.keypad().is_pressed(data_x) {
+                    self.program_counter.step(2)?;
+                }
+            },
+            OpLiteral::_EXA1 => {
+                // Skips the next instruction if the key stored in Vx is NOT pressed (usually the next instruction is a jump to skip a code block).
+
+                let register_x = opcode.x() as usize;
+                let data_x = self.data_registers.read_idx(register_x)?;
+
+                self.program_counter.step(2)?;
+
+                if !self.bus.keypad().is_pressed(data_x) {
+                    self.program_counter.step(2)?;
+                }
+            },
+            OpLiteral::_FX07 => {
+                // Set Vx to the value of the delay timer.
+                let register_x = opcode.x() as usize;
+                self.sync_timers();
+                self.data_registers.write_idx(register_x, self.bus.delay_timer().value())?;
+                self.program_counter.step(2)?;
+
+            },
+            OpLiteral::_FX0A => {
+                // A key press is awaited, and the stored in Vx (blocking operation, all instruction halted until next key event).
+                let register_x = opcode.x() as usize;
+
+                // Without a key, the program counter stays put and this runs again next cycle,
+                // so timers and rendering keep going while we wait. With the `fx0a_on_release`
+                // quirk, a pressed key is latched and only stored once it's let go.
+                let key = match self.fx0a_latched {
+                    Some(key) if self.bus.keypad().is_pressed(key) => None,
+                    Some(key) => {
+                        self.fx0a_latched = None;
+                        Some(key)
+                    },
+                    None => match self.bus.keypad_mut().read() {
+                        Some(key) if self.quirks.fx0a_on_release => {
+                            self.fx0a_latched = Some(key & 0x0F);
+                            None
+                        },
+                        key => key,
+                    },
+                };
+                if let Some(key) = key {
+                    self.data_registers.write_idx(register_x, key & 0x0F)?;
+                    self.program_counter.step(2)?;
+                }
+
+            }
+            OpLiteral::_FX15 => {
+                // Set the delay timer to Vx.
+
+                let register_x = opcode.x() as usize;
+                let data_x = self.data_registers.read_idx(register_x)?;
+                self.sync_timers();
+                self.bus.delay_timer_mut().reset(data_x);
+
+                self.program_counter.step(2)?;
+            },
+            OpLiteral::_FX18 => {
+                // Set the sound timer to Vx.
+
+                let register_x = opcode.x() as usize;
+                let data_x = self.data_registers.read_idx(register_x)?;
+                let ticks = if data_x > 0 { data_x.max(self.min_beep_ticks) } else { 0 };
+                self.sync_timers();
+                self.bus.sound_timer_mut().reset(ticks);
+
+                self.program_counter.step(2)?;
+            },
+            OpLiteral::_FX1E => {
+                // Adds Vx to I. Vf is unaffected. I wraps at its configured width.
+                let register_x = opcode.x() as usize;
+                let data_x = self.data_registers.read_idx(register_x)?;
+
+                self.address_register.write(
+                    self.address_register.read().wrapping_add(data_x as u16)
+                )?;
+
+                self.program_counter.step(2)?;
+            },
+            OpLiteral::_FX29 => {
+                // Sets I to the location of the sprite for the character in Vx. Characters 0-F (in hexadecimal) are represented by a 4x5 font.
+
+                let register_x = opcode.x() as usize;
+                let data_x = self.data_registers.read_idx(register_x)? & 0x0F;
+
+                let sprite_location = FONT_BASE + data_x as u16 * 5;
+                self.address_register.write(sprite_location)?;
+
+                self.program_counter.step(2)?;
+
+            },
+            OpLiteral::_FX33 => {
+                // Store the binary-coded decimal representation of VX, with the hundreds digit in memory at location in I,
+                // the tens digit at location I + 1, and the ones digit at location I + 2.
+
+                let data_x = self.data_registers.read_idx(opcode.x() as usize)?;
+                self.check_index_range(3)?;
+
+                self.bus.write(self.address_register.read() as usize, ((data_x as usize) / 100) as u8);
+                self.bus.write(self.address_register.read() as usize + 1, (((data_x as usize) / 10) % 10) as u8);
+                self.bus.write(self.address_register.read() as usize + 2, (((data_x as usize) % 100) % 10) as u8);
+                let address = self.address_register.read() as usize;
+                self.wrote_memory(address..address + 3);
+                
+                self.program_counter.step(2)?;
+
+            },
+            OpLiteral::_FX55 => {
+                // Stores from V0 to Vx (including Vx) in memory, starting at address I. The offset from I is increased by 1 for each value written, but I itself is left unmodified
+                // (unless the `index_increment` quirk is on, in which case I ends up at I + X + 1).
+
+                let register_breakpoint = opcode.x() as usize;
+                self.check_index_range(register_breakpoint + 1)?;
+                let mut current_address = self.address_register.read() as usize;
+
+                for register_idx in 0..=register_breakpoint {
+
+                    let to_store = self.data_registers.read_idx(register_idx)?;
+                    self.bus.write(current_address, to_store);
+                    current_address += 1;
+
+                }
+                self.wrote_memory(self.address_register.read() as usize..current_address);
+                if self.quirks.index_increment {
+                    self.address_register.write(current_address as u16)?;
+                }
+                self.program_counter.step(2)?;
+            },
+            OpLiteral::_FX65 => {
+                // Fills from V0 to Vx (including Vx) with values from memory, starting at address I. The offset from I is increased by 1 for each value read, but I itself is left unmodified
+                // (unless the `index_increment` quirk is on, in which case I ends up at I + X + 1).
+
+                let register_breakpoint = opcode.x() as usize;
+                self.check_index_range(register_breakpoint + 1)?;
+                let mut current_address = self.address_register.read() as usize;
+
+                for register_idx in 0..=register_breakpoint {
+
+                    let to_fill = self.bus.read(current_address);
+                    self.data_registers.write_idx(register_idx, to_fill)?;
+
+                    current_address += 1;
+                }
+                if self.quirks.index_increment {
+                    self.address_register.write(current_address as u16)?;
+                }
+                self.program_counter.step(2)?;
+            },
+            OpLiteral::_FX75 => {
+                // Stores V0 to Vx (including Vx) in the RPL user flags. There are only 8 of them, so X is clamped to 7.
+
+                let last_register = (opcode.x() as usize).min(7);
+                for register_idx in 0..=last_register {
+                    self.rpl_flags[register_idx] = self.data_registers.read_idx(register_idx)?;
+                }
+                self.program_counter.step(2)?;
+            },
+            OpLiteral::_FX85 => {
+                // Fills V0 to Vx (including Vx) from the RPL user flags. X is clamped to 7.
+
+                let last_register = (opcode.x() as usize).min(7);
+                for register_idx in 0..=last_register {
+                    self.data_registers.write_idx(register_idx, self.rpl_flags[register_idx])?;
+                }
+                self.program_counter.step(2)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Execute a single instruction. Does nothing once the program has halted.
+    pub fn step(&mut self) -> Result<(), Chip8Error>{
+        if self.finish_micro_step()? {
+            return Ok(());
+        }
+        if self.halted {
+            return Ok(());
+        }
+        if let Some((idx, word)) = self.find_custom_opcode() {
+            self.record_snapshot();
+            return self.run_custom_opcode(idx, word);
+        }
+        let pc = self.program_counter.read();
+        let decoded = match self.decode_cache[pc as usize] {
+            Some(decoded) => decoded,
+            None => match OpCode::try_from(self.fetch_opcode()) {
+                Ok(decoded) => {
+                    self.decode_cache[pc as usize] = Some(decoded);
+                    decoded
+                },
+                Err(OpCodeError::Unknown(word)) if self.illegal_opcode_policy == IllegalOpcodePolicy::Skip => {
+                    log::warn!("Skipped the illegal opcode {:#06x} at {:#05x}.", word, pc);
+                    self.record_snapshot();
+                    self.program_counter.step(2)?;
+                    self.cycles += 1;
+                    return Ok(());
+                },
+                Err(err) => return Err(err.into()),
+            },
+        };
+        self.record_snapshot();
+
+        // Hooks are taken out for the call, since they borrow the whole machine.
+        if let Some(mut hook) = self.pre_exec_hook.take() {
+            (hook.0)(self, decoded);
+            self.pre_exec_hook = Some(hook);
+        }
+        self.check_vf_tripwire(pc, &decoded);
+        self.apply_opcode(decoded.value)?;
+        self.executed[decoded.literal as usize] = true;
+
+        let jumped = matches!(decoded.literal, OpLiteral::_1NNN | OpLiteral::_2NNN | OpLiteral::_00EE | OpLiteral::_BNNN);
+        if !jumped && self.program_counter.read() < pc {
+            log::warn!("The program counter ran off the end of memory at {:#05x} and wrapped to {:#05x}.", pc, self.program_counter.read());
+        }
+
+        if let Some(trace) = &mut self.trace {
+            trace.record(TraceEntry { pc, opcode: decoded, registers: self.data_registers.values() });
+        }
+        self.cycles += 1;
+        self.check_display_watchdog();
+
+        if let Some(mut hook) = self.post_exec_hook.take() {
+            let info = StepInfo { pc, next_pc: self.program_counter.read(), cycles: self.cycles };
+            (hook.0)(self, decoded, &info);
+            self.post_exec_hook = Some(hook);
+        }
+        Ok(())
+
+    }
+
+    /// `step`, unless a breakpoint or halt is in the way. Reports anything that
+    /// should hand control back to the caller.
+    fn step_or_stop(&mut self) -> Result<Option<StopReason>, Chip8Error> {
+        let pc = self.program_counter.read();
+        if self.take_watchdog_stop() {
+            return Ok(Some(StopReason::NoDisplayActivity));
+        }
+        if self.halted {
+            return Ok(Some(StopReason::Halted));
+        }
+        if self.resume_from.take() != Some(pc) && self.breakpoints.contains(&pc) {
+            self.resume_from = Some(pc);
+            return Ok(Some(StopReason::Breakpoint(pc)));
+        }
+        if !self.is_initialized(pc as usize) || !self.is_initialized(pc as usize + 1) {
+            return Ok(Some(StopReason::UninitializedFetch(pc)));
+        }
+
+        let was_beeping = self.sound_timer() > 0;
+        self.step()?;
+        if self.halted {
+            return Ok(Some(StopReason::Halted));
+        }
+        for (address, last) in &mut self.watchpoints {
+            let value = self.bus.read(*address as usize);
+            if value != *last {
+                let old = core::mem::replace(last, value);
+                return Ok(Some(StopReason::Watchpoint { address: *address, old, new: value }));
+            }
+        }
+        if self.take_watchdog_stop() {
+            return Ok(Some(StopReason::NoDisplayActivity));
+        }
+        Ok(self.beep_stop(was_beeping))
+    }
+
+    /// The stop reason for the tone starting or stopping, if beep stops are on.
+    fn beep_stop(&self, was_beeping: bool) -> Option<StopReason> {
+        let beeping = self.sound_timer() > 0;
+        match (self.beep_stops, was_beeping, beeping) {
+            (true, false, true) => Some(StopReason::BeepStarted),
+            (true, true, false) => Some(StopReason::BeepEnded),
+            _ => None,
+        }
+    }
+
+    /// Step `cycles` instructions, unless a breakpoint, watchpoint or halt stops it sooner.
+    pub fn run_cycles(&mut self, cycles: usize) -> Result<StopReason, Chip8Error> {
+        for _ in 0..cycles {
+            if let Some(reason) = self.step_or_stop()? {
+                return Ok(reason);
+            }
+        }
+        Ok(StopReason::CycleLimit)
+    }
+
+    /// Execute `cycles` instructions as fast as possible, for measuring the interpreter itself.
+    /// Unlike `run_cycles` nothing else happens: no breakpoints, watchpoints, sanitizer, hooks,
+    /// tracing, rewind or coverage, and the timers, keypad and redraw flag are left alone. Stops
+    /// early if the program halts.
+    pub fn bench_run(&mut self, cycles: usize) -> Result<(), Chip8Error> {
+        for _ in 0..cycles {
+            if self.halted {
+                break;
+            }
+            if let Some((idx, word)) = self.find_custom_opcode() {
+                self.run_custom_opcode(idx, word)?;
+                continue;
+            }
+            let pc = self.program_counter.read() as usize;
+            let decoded = match self.decode_cache[pc] {
+                Some(decoded) => decoded,
+                None => {
+                    let decoded = OpCode::try_from(self.fetch_opcode())?;
+                    self.decode_cache[pc] = Some(decoded);
+                    decoded
+                }
+            };
+            self.apply_opcode(decoded.value)?;
+            self.cycles += 1;
+        }
+        Ok(())
+    }
+
+    /// Step until an instruction changes the screen, or until `max_cycles` instructions have run.
+    /// The redraw flag is left set when this returns `StopReason::Drew`.
+    pub fn run_to_next_draw(&mut self, max_cycles: usize) -> Result<StopReason, Chip8Error> {
+        let pending = self.should_draw;
+        self.should_draw = false;
+
+        let mut reason = StopReason::CycleLimit;
+        for _ in 0..max_cycles {
+            if let Some(stop) = self.step_or_stop()? {
+                reason = stop;
+                break;
+            }
+            if self.should_draw {
+                reason = StopReason::Drew;
+                break;
+            }
+        }
+        self.should_draw |= pending;
+        Ok(reason)
+    }
+
+    /// Step until the current subroutine returns, i.e. until the call stack is shallower than it
+    /// is now, or until `max_cycles` instructions have run. The program counter is then at the
+    /// instruction after the call. Outside of a subroutine there's nothing to return from, so this
+    /// only stops for the usual reasons.
+    pub fn step_out(&mut self, max_cycles: usize) -> Result<StopReason, Chip8Error> {
+        let depth = self.stack_pointer;
+        for _ in 0..max_cycles {
+            if let Some(stop) = self.step_or_stop()? {
+                return Ok(stop);
+            }
+            if self.stack_pointer < depth {
+                return Ok(StopReason::Returned);
+            }
+        }
+        Ok(StopReason::CycleLimit)
+    }
+
+    /// Catch the timers up with the clock, if one was installed with `with_clock`.
+    pub fn sync_timers(&mut self) {
+        let Some(clock) = &self.clock else {
+            return;
+        };
+        let elapsed = clock.now().saturating_sub(self.last_timer_tick);
+        let ticks = elapsed.as_nanos() / TIMER_PERIOD.as_nanos();
+        self.last_timer_tick += TIMER_PERIOD * ticks as u32;
+
+        // Past 255 ticks both timers have run out anyway.
+        for _ in 0..ticks.min(u8::MAX as u128) {
+            self.tick_timers();
+        }
+    }
+
+    /// Decrement the delay and sound timers. Meant to be called at 60 Hz.
+    pub fn tick_timers(&mut self) {
+        if self.bus.delay_timer().value() > 0 {
+            self.bus.delay_timer_mut().tick();
+        }
+        if self.bus.sound_timer().value() > 0 {
+            self.bus.sound_timer_mut().tick();
+        }
+    }
+
+    /// Run one 60 Hz frame: `cycles_per_frame` instructions, then a timer tick.
+    /// The renderer is only called if the screen changed, and the tone follows the sound timer.
+    /// Does nothing while paused.
+    ///
+    /// Timers tick once, after all of the frame's instructions, so a timer set and read back
+    /// within one frame reads back unchanged. With a clock installed via `with_clock`, timers
+    /// follow the clock instead and can tick between instructions.
+    ///
+    /// Returns early with the reason if a breakpoint, watchpoint or halt cuts the frame short.
+    /// The timers don't tick for a cut-short frame, but the screen is still rendered. A frame
+    /// held to `with_max_cycles_per_frame` runs in full otherwise, then reports
+    /// `StopReason::CycleLimit`.
+    ///
+    /// Never reads the time itself: pacing frames is up to the host, e.g. a browser's
+    /// animation loop on wasm.
+    pub fn run_frame<R: Renderer, A: Audio>(&mut self, renderer: &mut R, audio: &mut A) -> Result<Option<StopReason>, Chip8Error> {
+        if self.paused {
+            return Ok(None);
+        }
+        self.execute_frame(renderer, audio)
+    }
+
+    /// Run exactly one frame, even while paused.
+    pub fn frame_advance<R: Renderer, A: Audio>(&mut self, renderer: &mut R, audio: &mut A) -> Result<Option<StopReason>, Chip8Error> {
+        self.execute_frame(renderer, audio)
+    }
+
+    fn execute_frame<R: Renderer, A: Audio>(&mut self, renderer: &mut R, audio: &mut A) -> Result<Option<StopReason>, Chip8Error> {
+        self.validate_timing()?;
+        let capped = self.cycles_per_frame() > self.max_cycles_per_frame;
+        for _ in 0..self.cycles_per_frame().min(self.max_cycles_per_frame) {
+            if let Some(reason) = self.step_or_stop()? {
+                if self.take_redraw() {
+                    renderer.render(self.bus.screen());
+                }
+                return Ok(Some(reason));
+            }
+        }
+        let was_beeping = self.sound_timer() > 0;
+        if self.clock.is_some() {
+            self.sync_timers();
+        } else {
+            self.tick_timers();
+        }
+
+        let beeping = self.bus.sound_timer().value() > 0;
+        if beeping && !self.tone_playing {
+            audio.start_tone();
+        } else if !beeping && self.tone_playing {
+            audio.stop_tone();
+        }
+        self.tone_playing = beeping;
+
+        if self.take_redraw() {
+            renderer.render(self.bus.screen());
+        }
+        Ok(self.beep_stop(was_beeping).or(capped.then_some(StopReason::CycleLimit)))
+    }
+
+    /// Apply pending input to the keypad. Returns `false` once the user asked to quit.
+    /// Hosts driving `run_frame` themselves should call this once per frame.
+    pub fn service_input<I: InputSource>(&mut self, input: &mut I) -> bool {
+        self.bus.keypad_mut().apply_input(input)
+    }
+
+    /// Run frames at 60 Hz until the input source asks to quit, or a frame stops early.
+    #[cfg(feature = "std")]
+    pub fn run<R: Renderer, A: Audio, I: InputSource>(&mut self, renderer: &mut R, audio: &mut A, input: &mut I) -> Result<StopReason, Chip8Error> {
+        let frame = std::time::Duration::from_secs(1) / 60;
+        let reason = loop {
+            if !self.service_input(input) {
+                break StopReason::UserQuit;
+            }
+            let started = std::time::Instant::now();
+            if let Some(reason) = self.run_frame(renderer, audio)? {
+                break reason;
+            }
+            if let Some(remaining) = frame.checked_sub(started.elapsed()) {
+                std::thread::sleep(remaining);
+            }
+        };
+        if self.tone_playing {
+            audio.stop_tone();
+            self.tone_playing = false;
+        }
+        Ok(reason)
+    }
+
+    /// Run frames back to back, without a display or sound, until `timeout` has passed or a frame
+    /// stops early. Time is read from the clock installed with `with_clock`, or the wall clock
+    /// if there is none; frames aren't paced, so this runs as fast as the host allows.
+    ///
+    /// A frame during which the clock stood still, e.g. a `MockClock` nobody advances, counts
+    /// as one `TIMER_PERIOD`. A paused machine can't get anywhere, so it times out straight away.
+    #[cfg(feature = "std")]
+    pub fn run_with_timeout(&mut self, timeout: Duration) -> Result<StopReason, Chip8Error> {
+        let wall_clock = super::SystemClock::default();
+        let now = |chip: &Self| chip.clock.as_ref().map_or_else(|| wall_clock.now(), |clock| clock.now());
+
+        let started = now(self);
+        let mut still_frames = Duration::ZERO;
+        while !self.paused && now(self) - started + still_frames < timeout {
+            let frame_started = now(self);
+            if let Some(reason) = self.run_frame(&mut (), &mut ())? {
+                return Ok(reason);
+            }
+            if now(self) == frame_started {
+                still_frames += TIMER_PERIOD;
+            }
+        }
+        Ok(StopReason::Timeout)
+    }
+
+    /// Run in the terminal until Esc or Ctrl-C, restoring the terminal on the way out.
+    #[cfg(feature = "std")]
+    pub fn start(&mut self) -> Result<StopReason, Chip8Error>{
+        self.start_with_keymap(HexKeyMap::default(), false)
+    }
+
+    /// `start`, with the keyboard mapped to the keypad by `keymap` instead of the default layout,
+    /// and the terminal bell ringing for beeps if `bell` is set.
+    #[cfg(feature = "std")]
+    pub fn start_with_keymap(&mut self, keymap: HexKeyMap, bell: bool) -> Result<StopReason, Chip8Error>{
+        let _terminal = super::TerminalGuard::new()?;
+        let mut input = super::CrosstermInput::default();
+        input.keymap = keymap;
+        let mut renderer = super::TerminalRenderer::default();
+        if bell {
+            self.run(&mut renderer, &mut super::TerminalBell::default(), &mut input)
+        } else {
+            self.run(&mut renderer, &mut (), &mut input)
+        }
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use super::super::KeyEvent;
+
+    /// What `representative`'s instruction should leave behind, or `None` if it should fail.
+    type Effect = Option<fn(&Chip8) -> bool>;
+
+    /// A representative instruction for each literal, and its effect on a machine set up by
+    /// `representative_machine`. Operands avoid V0 so that a handler reading the wrong nibble
+    /// shows up. The match has no wildcard, so a new literal doesn't compile until it's listed
+    /// here too.
+    fn representative(literal: OpLiteral) -> (u16, Effect) {
+        fn pc(chip: &Chip8) -> u16 {
+            chip.program_counter.read()
+        }
+        fn v(chip: &Chip8, idx: usize) -> u8 {
+            chip.data_registers.values()[idx]
+        }
+        fn mem(chip: &Chip8, start: usize, len: usize) -> Vec<u8> {
+            (start..start + len).map(|address| chip.bus.read(address)).collect()
+        }
+        match literal {
+            // No machine routine is installed, so this only moves on.
+            OpLiteral::_0NNN => (0x0300, Some(|chip| pc(chip) == 0x202)),
+            OpLiteral::_00E0 => (0x00E0, Some(|chip| pc(chip) == 0x202 && chip.should_draw)),
+            // Nothing on the stack to return to.
+            OpLiteral::_00EE => (0x00EE, None),
+            OpLiteral::_00CN => (0x00C4, Some(|chip| pc(chip) == 0x202 && chip.should_draw)),
+            OpLiteral::_00DN => (0x00D4, Some(|chip| pc(chip) == 0x202 && chip.should_draw)),
+            OpLiteral::_00FD => (0x00FD, Some(|chip| pc(chip) == 0x200 && chip.is_halted())),
+            OpLiteral::_00FE => (0x00FE, Some(|chip| pc(chip) == 0x202 && !chip.bus.screen().is_hires())),
+            OpLiteral::_00FF => (0x00FF, Some(|chip| pc(chip) == 0x202 && chip.bus.screen().is_hires())),
+            OpLiteral::_1NNN => (0x1300, Some(|chip| pc(chip) == 0x300)),
+            OpLiteral::_2NNN => (0x2300, Some(|chip| pc(chip) == 0x300 && chip.call_stack() == [0x200])),
+            // V5 is 0x12, so both skip tests are decided by V5 and not V0.
+            OpLiteral::_3XNN => (0x3512, Some(|chip| pc(chip) == 0x204)),
+            OpLiteral::_4XNN => (0x4512, Some(|chip| pc(chip) == 0x202)),
+            OpLiteral::_5XY0 => (0x5560, Some(|chip| pc(chip) == 0x202)),
+            OpLiteral::_6XNN => (0x6577, Some(|chip| v(chip, 5) == 0x77)),
+            OpLiteral::_7XNN => (0x75FF, Some(|chip| v(chip, 5) == 0x11 && v(chip, 15) == 0)),
+            OpLiteral::_8XY0 => (0x8560, Some(|chip| v(chip, 5) == 0x34)),
+            OpLiteral::_8XY1 => (0x8561, Some(|chip| v(chip, 5) == 0x36)),
+            OpLiteral::_8XY2 => (0x8562, Some(|chip| v(chip, 5) == 0x10)),
+            OpLiteral::_8XY3 => (0x8563, Some(|chip| v(chip, 5) == 0x26)),
+            OpLiteral::_8XY4 => (0x8564, Some(|chip| v(chip, 5) == 0x46 && v(chip, 15) == 0)),
+            OpLiteral::_8XY5 => (0x8565, Some(|chip| v(chip, 5) == 0xDE && v(chip, 15) == 0)),
+            OpLiteral::_8XY6 => (0x8566, Some(|chip| v(chip, 5) == 0x09 && v(chip, 15) == 0)),
+            OpLiteral::_8XY7 => (0x8567, Some(|chip| v(chip, 5) == 0x22 && v(chip, 15) == 1)),
+            OpLiteral::_8XYE => (0x856E, Some(|chip| v(chip, 5) == 0x24 && v(chip, 15) == 0)),
+            OpLiteral::_9XY0 => (0x9560, Some(|chip| pc(chip) == 0x204)),
+            OpLiteral::_ANNN => (0xA123, Some(|chip| chip.address_register.read() == 0x123)),
+            OpLiteral::_BNNN => (0xB300, Some(|chip| pc(chip) == 0x300)),
+            // NN masks out every random bit.
+            OpLiteral::_CXNN => (0xC500, Some(|chip| v(chip, 5) == 0 && pc(chip) == 0x202)),
+            OpLiteral::_DXYN => (0xD565, Some(|chip| chip.should_draw && chip.bus.screen() != &Screen::default())),
+            // V5 holds key 2, which isn't pressed.
+            OpLiteral::_EX9E => (0xE59E, Some(|chip| pc(chip) == 0x202)),
+            OpLiteral::_EXA1 => (0xE5A1, Some(|chip| pc(chip) == 0x204)),
+            OpLiteral::_FX07 => (0xF507, Some(|chip| v(chip, 5) == 0)),
+            // No key is pressed, so it waits.
+            OpLiteral::_FX0A => (0xF50A, Some(|chip| pc(chip) == 0x200)),
+            OpLiteral::_FX15 => (0xF515, Some(|chip| chip.delay_timer() == 0x12)),
+            OpLiteral::_FX18 => (0xF518, Some(|chip| chip.sound_timer() == 0x12)),
+            OpLiteral::_FX1E => (0xF51E, Some(|chip| chip.address_register.read() == 0x312)),
+            OpLiteral::_FX29 => (0xF529, Some(|chip| chip.address_register.read() == FONT_BASE + 2 * 5)),
+            // V7 is 255.
+            OpLiteral::_FX33 => (0xF733, Some(|chip| mem(chip, 0x300, 3) == [2, 5, 5])),
+            OpLiteral::_FX55 => (0xF655, Some(|chip| mem(chip, 0x300, 7) == [0, 0, 0, 0, 0, 0x12, 0x34])),
+            OpLiteral::_FX65 => (0xF265, Some(|chip| chip.data_registers.values()[..3] == [1, 2, 3])),
+            OpLiteral::_FX75 => (0xF575, Some(|chip| chip.rpl_flags == [0, 0, 0, 0, 0, 0x12, 7, 8])),
+            OpLiteral::_FX85 => (0xF585, Some(|chip| chip.data_registers.values()[..6] == [1, 2, 3, 4, 5, 6])),
+        }
+    }
+
+    /// A fresh machine with V5 = 0x12, V6 = 0x34, V7 = 255, I at 0x300 holding 1, 2, 3, and the
+    /// RPL flags holding 1 to 8.
+    fn representative_machine() -> Result<Chip8, Chip8Error> {
+        let mut chip = Chip8::new();
+        chip.data_registers.write_idx(5, 0x12)?;
+        chip.data_registers.write_idx(6, 0x34)?;
+        chip.data_registers.write_idx(7, 0xFF)?;
+        chip.address_register.write(0x300)?;
+        chip.bus.load(0x300, &[1, 2, 3]);
+        chip.rpl_flags = [1, 2, 3, 4, 5, 6, 7, 8];
+        Ok(chip)
+    }
+
+    #[test]
+    fn every_literal_has_a_handler() -> Result<(), Chip8Error> {
+        for &literal in OpLiteral::all() {
+            let (word, effect) = representative(literal);
+            assert_eq!(OpCode::try_from(word)?.literal, literal);
+
+            let mut chip = representative_machine()?;
+            let result = chip.apply_opcode(word);
+            assert!(!matches!(result, Err(Chip8Error::OpCode(OpCodeError::Unknown(_)))), "{:?}", literal);
+            match effect {
+                Some(effect) => {
+                    assert!(result.is_ok(), "{:?}: {:?}", literal, result);
+                    assert!(effect(&chip), "{:?} had the wrong effect", literal);
+                },
+                None => assert!(result.is_err(), "{:?} should have failed", literal),
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn sprite_past_the_end_of_memory() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::new();
+        chip.bus.write(0xFFE, 0xFF);
+        chip.bus.write(0xFFF, 0xFF);
+        chip.address_register.write(0xFFE)?;
+
+        // Only the two rows inside memory are drawn.
+        chip.apply_opcode(0xD018)?;
+        let lit: Vec<usize> = (0..chip.bus.screen().len()).filter(|pixel| chip.bus.screen()[*pixel]).collect();
+        assert_eq!(lit, (0..8).chain(64..72).collect::<Vec<_>>());
+
+        let mut chip = Chip8::new().with_sprite_overrun(SpriteOverrun::Error);
+        chip.address_register.write(0xFFE)?;
+        let pc = chip.program_counter.read();
+        assert!(matches!(chip.apply_opcode(0xD018), Err(Chip8Error::Memory(MemoryError::OutOfBounds(0x1005)))));
+        assert_eq!(chip.program_counter.read(), pc);
+        assert_eq!(chip.bus.screen(), &Screen::default());
+        Ok(())
+    }
+
+    #[test]
+    fn bench_run_matches_run_cycles() -> Result<(), Chip8Error> {
+        let rom = include_bytes!("../../programs/tetris.c8");
+        let mut stepped = Chip8::from_rom_bytes(rom)?.with_rng_seed(1);
+        let mut benched = Chip8::from_rom_bytes(rom)?.with_rng_seed(1);
+
+        stepped.run_cycles(5_000)?;
+        benched.bench_run(5_000)?;
+
+        assert_eq!(benched.save_state_bytes(), stepped.save_state_bytes());
+        assert_eq!(benched.cycles(), stepped.cycles());
+        Ok(())
+    }
+
+    #[test]
+    fn timers_are_readable() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::from_rom_bytes(&[
+            0x60, 0x2A, // V0 = 42
+            0xF0, 0x15, // delay timer = V0
+            0xF0, 0x18, // sound timer = V0
+        ])?;
+        chip.run_cycles(3)?;
+        assert_eq!((chip.delay_timer(), chip.sound_timer()), (42, 42));
+
+        chip.tick_timers();
+        assert_eq!((chip.delay_timer(), chip.sound_timer()), (41, 41));
+        Ok(())
+    }
+
+    #[test]
+    fn fx0a_on_release_quirk() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::from_rom_bytes(&[0xF3, 0x0A])?
+            .with_quirks(Quirks { fx0a_on_release: true, ..Quirks::default() });
+
+        chip.bus.keypad_mut().set(5, true);
+        chip.step()?;
+        chip.step()?;
+        assert_eq!(chip.data_registers.read('3')?, 0);
+        assert_eq!(chip.program_counter.read(), 0x200);
+
+        chip.bus.keypad_mut().set(5, false);
+        chip.step()?;
+        assert_eq!(chip.data_registers.read('3')?, 5);
+        assert_eq!(chip.program_counter.read(), 0x202);
+
+        let mut chip = Chip8::from_rom_bytes(&[0xF3, 0x0A])?;
+        chip.bus.keypad_mut().set(5, true);
+        chip.step()?;
+        assert_eq!(chip.data_registers.read('3')?, 5);
+        assert_eq!(chip.program_counter.read(), 0x202);
+        Ok(())
+    }
+
+    #[test]
+    fn high_water_marks() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::from_rom_bytes(&[
+            0x22, 0x04, // call 0x204
+            0x12, 0x02, // loop in place
+            0x22, 0x08, // call 0x208
+            0x00, 0xEE, // return
+            0x22, 0x0C, // call 0x20C
+            0x00, 0xEE, // return
+            0xA3, 0x00, // I = 0x300
+            0xF2, 0x55, // store V0 to V2 at I
+            0x00, 0xEE, // return
+        ])?;
+        assert_eq!((chip.max_stack_depth(), chip.max_memory_write()), (0, None));
+
+        chip.run_cycles(20)?;
+        assert_eq!(chip.max_stack_depth(), 3);
+        assert_eq!(chip.call_stack().len(), 0);
+        assert_eq!(chip.max_memory_write(), Some(0x302));
+        Ok(())
+    }
+
+    #[test]
+    fn custom_opcode_handler() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::from_rom_bytes(&[
+            0xF3, 0xFF, // not a built-in instruction
+            0x60, 0x01, // V0 = 1
+        ])?;
+        chip.register_opcode(0xF0FF, 0xF0FF, |chip, word| {
+            chip.data_registers.write_idx(((word >> 8) & 0xF) as usize, 0x42)?;
+            chip.program_counter.step(2)?;
+            Ok(())
+        });
+
+        chip.run_cycles(2)?;
+        assert_eq!(chip.data_registers.read('3')?, 0x42);
+        assert_eq!(chip.data_registers.read('0')?, 1);
+        assert_eq!(chip.cycles(), 2);
+        Ok(())
+    }
+
+    /// Keeps every warning logged while tests run, from any thread.
+    struct CapturingLogger(std::sync::Mutex<Vec<String>>);
+
+    impl log::Log for CapturingLogger {
+        fn enabled(&self, metadata: &log::Metadata) -> bool {
+            metadata.level() <= log::Level::Warn
+        }
+        fn log(&self, record: &log::Record) {
+            if self.enabled(record.metadata()) {
+                self.0.lock().unwrap().push(record.args().to_string());
+            }
+        }
+        fn flush(&self) {}
+    }
+
+    static LOGGER: CapturingLogger = CapturingLogger(std::sync::Mutex::new(Vec::new()));
+
+    #[test]
+    fn skipped_illegal_opcode_is_logged() -> Result<(), Chip8Error> {
+        let _ = log::set_logger(&LOGGER);
+        log::set_max_level(log::LevelFilter::Warn);
+
+        let mut chip = Chip8::from_rom_bytes(&[
+            0xF0, 0xFF, // not an instruction
+            0x60, 0x01, // V0 = 1
+        ])?.with_illegal_opcode_policy(IllegalOpcodePolicy::Skip);
+        chip.run_cycles(2)?;
+
+        assert_eq!(chip.data_registers.read('0')?, 1);
+        let warnings = LOGGER.0.lock().unwrap();
+        assert!(warnings.iter().any(|warning| warning == "Skipped the illegal opcode 0xf0ff at 0x200."), "{:?}", warnings);
+        Ok(())
+    }
+
+    #[test]
+    fn vf_tripwire_catches_a_clobbered_scratch_value() -> Result<(), Chip8Error> {
+        let _ = log::set_logger(&LOGGER);
+        log::set_max_level(log::LevelFilter::Warn);
+
+        let mut chip = Chip8::from_rom_bytes(&[
+            0x6F, 0x05, // VF = 5, as scratch
+            0x60, 0xFF, // V0 = 0xFF
+            0x80, 0x04, // V0 += V0, which sets VF to the carry
+            0x71, 0x01, // V1 += 1, leaves VF alone
+            0x3F, 0x05, // skip if VF is still 5
+        ])?.with_vf_tripwire();
+        chip.run_cycles(5)?;
+
+        let warnings = LOGGER.0.lock().unwrap();
+        let expected = "VF is read at 0x208, but the flag set at 0x204 replaced what the program stored in it.";
+        assert!(warnings.iter().any(|warning| warning == expected), "{:?}", warnings);
+        Ok(())
+    }
+
+    #[test]
+    fn display_watchdog_fires_on_time() -> Result<(), Chip8Error> {
+        let _ = log::set_logger(&LOGGER);
+        log::set_max_level(log::LevelFilter::Warn);
+
+        let rom = [
+            0x70, 0x01, // V0 += 1
+            0x12, 0x00, // forever, never drawing
+        ];
+        let mut chip = Chip8::from_rom_bytes(&rom)?.with_display_watchdog(25, WatchdogAction::Stop);
+        assert_eq!(chip.run_cycles(100)?, StopReason::NoDisplayActivity);
+        assert_eq!(chip.cycles(), 25);
+        assert_eq!(chip.run_cycles(100)?, StopReason::CycleLimit);
+
+        let warnings = LOGGER.0.lock().unwrap();
+        assert!(warnings.iter().any(|warning| warning.starts_with("Nothing has been drawn in 25 cycles.")), "{:?}", warnings);
+        drop(warnings);
+
+        let mut drawing = Chip8::from_rom_bytes(&[0x00, 0xE0, 0x12, 0x02])?.with_display_watchdog(25, WatchdogAction::Stop);
+        assert_eq!(drawing.run_cycles(100)?, StopReason::CycleLimit);
+
+        // A watchpoint on the limit cycle goes first, then the watchdog.
+        let watched = [
+            0x70, 0x01, // V0 += 1
+            0xA3, 0x00, // I = 0x300
+            0xF0, 0x55, // memory[0x300] = V0
+            0x12, 0x00, // forever
+        ];
+        let mut chip = Chip8::from_rom_bytes(&watched)?.with_display_watchdog(3, WatchdogAction::Stop);
+        chip.add_watchpoint(0x300);
+        assert!(matches!(chip.run_cycles(100)?, StopReason::Watchpoint { address: 0x300, .. }));
+        assert_eq!(chip.cycles(), 3);
+        assert_eq!(chip.run_cycles(100)?, StopReason::NoDisplayActivity);
+        assert_eq!(chip.cycles(), 3);
+
+        // Set up late, or only stepped, it still fires once.
+        let mut chip = Chip8::from_rom_bytes(&rom)?;
+        chip.run_cycles(10)?;
+        let mut chip = chip.with_display_watchdog(5, WatchdogAction::Stop);
+        chip.step()?;
+        assert_eq!(chip.run_cycles(100)?, StopReason::NoDisplayActivity);
+        assert_eq!(chip.cycles(), 11);
+        assert_eq!(chip.run_cycles(100)?, StopReason::CycleLimit);
+        Ok(())
+    }
+
+    #[test]
+    fn beep_lasts_as_long_as_the_sound_timer() -> Result<(), Chip8Error> {
+        use crate::virtual_machine::MockClock;
+
+        let clock = MockClock::default();
+        let mut chip = Chip8::from_rom_bytes(&[
+            0x60, 0x06, // V0 = 6
+            0xF0, 0x18, // sound timer = V0
+            0x12, 0x04, // loop in place
+        ])?.with_clock(clock.clone()).with_beep_stops();
+
+        assert_eq!(chip.run_cycles(10)?, StopReason::BeepStarted);
+        assert_eq!(chip.cycles(), 2);
+
+        let mut elapsed = Duration::ZERO;
+        loop {
+            clock.advance(TIMER_PERIOD);
+            elapsed += TIMER_PERIOD;
+            if chip.run_frame(&mut (), &mut ())? == Some(StopReason::BeepEnded) {
+                break;
+            }
+        }
+        assert_eq!(elapsed, TIMER_PERIOD * 6);
+        Ok(())
+    }
+
+    #[test]
+    fn logic_vf_reset_quirk() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::new().with_quirks(Quirks { logic_vf_reset: true, ..Quirks::default() });
+        chip.data_registers.write('f', 1)?;
+
+        chip.apply_opcode(0x8011)?;
+
+        assert_eq!(chip.data_registers.read('f')?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn jump_vx_quirk() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::new().with_quirks(Quirks { jump_vx: true, ..Quirks::default() });
+        chip.data_registers.write('0', 0x01)?;
+        chip.data_registers.write('2', 0x10)?;
+
+        chip.apply_opcode(0xB234)?;
+
+        assert_eq!(chip.program_counter.read(), 0x234 + 0x10);
+        Ok(())
+    }
+
+    #[test]
+    fn index_increment_quirk() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::new().with_quirks(Quirks { index_increment: true, ..Quirks::default() });
+        chip.address_register.write(0x300)?;
+        chip.apply_opcode(0xF355)?;
+        assert_eq!(chip.address_register.read(), 0x304);
+
+        let mut chip = Chip8::new().with_quirks(Quirks { index_increment: false, ..Quirks::default() });
+        chip.address_register.write(0x300)?;
+        chip.apply_opcode(0xF355)?;
+        assert_eq!(chip.address_register.read(), 0x300);
+        Ok(())
+    }
+
+    #[test]
+    fn fx1e_wraps_i_at_the_address_width() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::new();
+        chip.address_register.write(0x0FFF)?;
+        chip.data_registers.write('0', 0x02)?;
+        chip.apply_opcode(0xF01E)?;
+        assert_eq!(chip.address_register.read(), 0x0001);
+
+        let mut chip = Chip8::new().with_address_width(AddressWidth::Bits16);
+        chip.address_register.write(0x0FFF)?;
+        chip.data_registers.write('0', 0x02)?;
+        chip.apply_opcode(0xF01E)?;
+        assert_eq!(chip.address_register.read(), 0x1001);
+        Ok(())
+    }
+
+    #[test]
+    fn clearing_the_screen_flags_one_redraw() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::new();
+        chip.bus.screen_mut()[5] = true;
+
+        chip.apply_opcode(0x00E0)?;
+        chip.apply_opcode(0x00E0)?;
+
+        assert!(chip.take_redraw());
+        assert!(!chip.take_redraw());
+        assert!((0..chip.bus.screen().len()).all(|pixel| !chip.bus.screen()[pixel]));
+        Ok(())
+    }
+
+    #[test]
+    fn displays_must_fit_a_savestate() -> Result<(), Chip8Error> {
+        for (width, height) in [(0, 32), (64, 0), (256, 32), (64, 256), (255, 255)] {
+            assert!(matches!(
+                Chip8::new().with_display(DisplayConfig { width, height }),
+                Err(Chip8Error::Screen(ScreenError::UnsupportedSize { .. }))
+            ));
+        }
+
+        let mut chip = Chip8::new().with_display(DisplayConfig { width: 255, height: 8 })?;
+        let state = chip.save_state_bytes();
+        chip.load_state_bytes(&state)?;
+        assert_eq!((chip.bus.screen().width(), chip.bus.screen().height()), (255, 8));
+        Ok(())
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn with_display_keeps_the_external_buffer() -> Result<(), Chip8Error> {
+        use std::sync::{Arc, Mutex};
+
+        let buffer = Arc::new(Mutex::new(Vec::new()));
+        let mut chip = Chip8::new();
+        *chip.bus_mut().screen_mut() = Screen::with_buffer(buffer.clone(), 64, 32);
+        let mut chip = chip.with_display(DisplayConfig::ETI660)?;
+        chip.initialize();
+        assert_eq!(buffer.lock().unwrap().len(), 64 * 48);
+
+        // The top row of the font's 0, on the bottom row of the screen.
+        chip.data_registers.write('1', 47)?;
+        chip.apply_opcode(0xD015)?;
+        assert!(buffer.lock().unwrap()[47 * 64]);
+        Ok(())
+    }
+
+    #[test]
+    fn draws_on_a_64x48_display() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::new().with_display(DisplayConfig::ETI660)?;
+        chip.initialize();
+        assert_eq!(chip.bus.screen().len(), 64 * 48);
+
+        // The font's 0 at y = 47: only its top row fits.
+        chip.data_registers.write('1', 47)?;
+        chip.apply_opcode(0xD015)?;
+        let screen = chip.bus.screen();
+        assert!((0..4).all(|x| screen[47 * 64 + x]));
+        assert!((0..64 * 47).all(|pixel| !screen[pixel]));
+        assert_eq!(&Screen::from_ascii(&screen.to_ascii('#', '.'), '#').unwrap(), screen);
+
+        chip.apply_opcode(0x00FF)?;
+        chip.apply_opcode(0x00FE)?;
+        assert_eq!((chip.bus.screen().width(), chip.bus.screen().height()), (64, 48));
+        Ok(())
+    }
+
+    #[test]
+    fn font_sprites_draw_their_glyph() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::new();
+        chip.initialize();
+        chip.data_registers.write('0', 0x7)?;
+
+        chip.apply_opcode(0xF029)?;
+        chip.apply_opcode(0xD115)?;
+
+        let glyph = &FontSet::default().0[7 * 5..8 * 5];
+        let screen = chip.bus.screen();
+        for (y, row) in glyph.iter().enumerate() {
+            for x in 0..8 {
+                assert_eq!(screen[x + y * 64], row & (0x80 >> x) != 0, "pixel ({}, {})", x, y);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn shift_vy_quirk() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::new().with_quirks(Quirks { shift_vy: true, ..Quirks::default() });
+        chip.data_registers.write('0', 0x00)?;
+        chip.data_registers.write('1', 0x02)?;
 
-                let quad: NibblePair = register_identifier.into();
-                let register_identifier = quad.low.to_hex_char();
-                let data = (opcode.value & 0x00FF) as u8;
-                
-                self.program_counter.step(2)?;
+        chip.apply_opcode(0x8016)?;
 
-                if let Ok(value_in_register) = self.data_registers.read(register_identifier) {
-                    if value_in_register != data {
-                        // Skip next instruction if (Vx != NN).
-                        self.program_counter.step(2)?;
-                    }
-                }
-            },
-            OpLiteral::_5XY0 => {
-                // Skips the next instruction if VX equals Vy (usually the next instruction is a jump to skip a code block.)
-                let register_x = ((opcode.value & 0x0F00) >> 8) as u8;
-                let register_y = ((opcode.value & 0x00F0) >> 4) as u8;
-                
-                let data_x = self.data_registers.read(NibblePair::from(register_x).low.to_hex_char())?;
-                let data_y = self.data_registers.read(NibblePair::from(register_y).low.to_hex_char())?;
-                
-                self.program_counter.step(2)?;
+        assert_eq!(chip.data_registers.read('0')?, 0x01);
+        assert_eq!(chip.data_registers.read('f')?, 0);
+        Ok(())
+    }
 
-                if data_x == data_y {
-                    self.program_counter.step(2)?;
-                }
-            },
-            OpLiteral::_6XNN => {
-                // Sets Vx to NN.
+    #[test]
+    fn sprite_wrap_quirk() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::new().with_quirks(Quirks { sprite_wrap: true, ..Quirks::default() });
+        chip.bus.write(0x300, 0xFF);
+        chip.address_register.write(0x300)?;
+        chip.data_registers.write('0', 60)?;
+        chip.data_registers.write('1', 3)?;
+        chip.bus.screen_mut()[2 + 3 * 64] = true;
 
-                let nn = (opcode.value & 0x00FF) as u8;
-                
-                let register_x = NibblePair::from(((opcode.value & 0x0F00) >> 8) as u8).low.to_hex_char();
+        chip.apply_opcode(0xD011)?;
 
-                self.data_registers.write(register_x, nn)?;
-                self.program_counter.step(2)?;
-            },
-            OpLiteral::_7XNN => {
-                // Adds NN to Vx (carry flag is not changed).
-                let nn = (opcode.value & 0x00FF) as u8;
-                let register_x = NibblePair::from(((opcode.value & 0x0F00) >> 8) as u8).low.to_hex_char();
+        let screen = chip.bus.screen();
+        for x in 60..64 {
+            assert!(screen[x + 3 * 64]);
+        }
+        for x in [0, 1, 3] {
+            assert!(screen[x + 3 * 64]);
+        }
+        // Already on, so the wrapped part of the sprite turned it off and collided.
+        assert!(!screen[2 + 3 * 64]);
+        assert!(!screen[4 + 3 * 64]);
+        assert_eq!(chip.data_registers.read('f')?, 1);
+        Ok(())
+    }
 
-                self.data_registers.write(
-                    register_x, 
-                    self.data_registers.read(register_x)? + nn
-                )?;
-                self.program_counter.step(2)?;
-            },
-            OpLiteral::_8XY0 => {
-                // Sets Vx to the value of Vy.
-                let register_x = NibblePair::from(((opcode.value & 0x0F00) >> 8) as u8).low.to_hex_char();
-                let register_y = NibblePair::from(((opcode.value & 0x00F0) >> 4) as u8).low.to_hex_char();
+    #[test]
+    fn sprites_clip_at_the_edge_by_default() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::new();
+        chip.bus.write(0x300, 0xFF);
+        chip.address_register.write(0x300)?;
+        chip.data_registers.write('0', 60)?;
+        chip.bus.screen_mut()[2] = true;
+
+        chip.apply_opcode(0xD011)?;
+
+        let screen = chip.bus.screen();
+        assert!((60..64).all(|x| screen[x]));
+        assert!(screen[2]);
+        assert!(!screen[0] && !screen[3]);
+        assert_eq!(chip.data_registers.read('f')?, 0);
+        Ok(())
+    }
 
-                let data_y = self.data_registers.read(register_y)?;
-                self.data_registers.write(register_x, data_y)?;
-                
-                self.program_counter.step(2)?;
-            },
-            OpLiteral::_8XY1 => {
-                // Sets Vx to the value of Vx | Vy.
-                let register_x = NibblePair::from(((opcode.value & 0x0F00) >> 8) as u8).low.to_hex_char();
-                let register_y = NibblePair::from(((opcode.value & 0x00F0) >> 4) as u8).low.to_hex_char();
+    #[test]
+    fn call_stack_lists_callers_in_order() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::new();
+        chip.initialize();
+        chip.load_program_bytes(&[0x23, 0x00]);
+        chip.bus.load(0x300, &[0x24, 0x00]);
+        assert!(chip.call_stack().is_empty());
 
-                let data_y = self.data_registers.read(register_y)?;
-                let data_x = self.data_registers.read(register_x)?;
+        chip.step()?;
+        chip.step()?;
 
-                self.data_registers.write(register_x, data_y | data_x)?;
-                
-                self.program_counter.step(2)?;
-            },
-            OpLiteral::_8XY2 => {
-                // Sets Vx to the value of Vx & Vy.
-                let register_x = NibblePair::from(((opcode.value & 0x0F00) >> 8) as u8).low.to_hex_char();
-                let register_y = NibblePair::from(((opcode.value & 0x00F0) >> 4) as u8).low.to_hex_char();
+        assert_eq!(chip.call_stack(), &[0x200, 0x300]);
+        Ok(())
+    }
 
-                let data_y = self.data_registers.read(register_y)?;
-                let data_x = self.data_registers.read(register_x)?;
+    #[test]
+    fn run_stops_on_quit() -> Result<(), Chip8Error> {
+        use crate::virtual_machine::ProgrammaticInput;
 
-                self.data_registers.write(register_x, data_y & data_x)?;
-                
-                self.program_counter.step(2)?;
-            },
-            OpLiteral::_8XY3 => {
-                // Sets Vx to the value of Vx ^ Vy.
-                let register_x = NibblePair::from(((opcode.value & 0x0F00) >> 8) as u8).low.to_hex_char();
-                let register_y = NibblePair::from(((opcode.value & 0x00F0) >> 4) as u8).low.to_hex_char();
+        let mut chip = Chip8::new();
+        chip.initialize();
+        chip.load_program_bytes(&[0x12, 0x00]);
 
-                let data_y = self.data_registers.read(register_y)?;
-                let data_x = self.data_registers.read(register_x)?;
+        let mut input = ProgrammaticInput::default();
+        input.push(KeyEvent::Pressed(0x5));
+        input.push(KeyEvent::Quit);
 
-                self.data_registers.write(register_x, data_y ^ data_x)?;
-                
-                self.program_counter.step(2)?;
-            },
-            OpLiteral::_8XY4 => {
+        let reason = chip.run(&mut crate::test_support::MockRenderer::default(), &mut (), &mut input)?;
 
-                // Adds Vy to Vx. Vf is set to 1 when there's a carry, and to 0 when there is not.
-                let register_x = NibblePair::from(((opcode.value & 0x0F00) >> 8) as u8).low.to_hex_char();
-                let register_y = NibblePair::from(((opcode.value & 0x00F0) >> 4) as u8).low.to_hex_char();
+        assert_eq!(reason, StopReason::UserQuit);
+        assert!(chip.bus.keypad().is_pressed(0x5));
+        assert_eq!(chip.program_counter.read(), 0x200);
+        Ok(())
+    }
 
-                let data_y = self.data_registers.read(register_y)?;
-                let data_x = self.data_registers.read(register_x)?;
-                
-                // The sum exceeds u8::MAX, set the carry.
-                if (data_x as u16 + data_y as u16) > (u8::MAX as u16) {
-                    self.data_registers.write('f', 1)?;
-                } 
-                else {
-                    self.data_registers.write('f', 0)?;
-                }
+    #[test]
+    fn pause_and_frame_advance() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::new();
+        chip.initialize();
+        chip.load_program_bytes(&[0x12, 0x00]);
+        let mut renderer = crate::test_support::MockRenderer::default();
+        let per_frame = chip.cycles_per_frame() as u64;
+
+        chip.run_frame(&mut renderer, &mut ())?;
+        assert_eq!(chip.cycles(), per_frame);
+
+        chip.pause();
+        assert!(chip.is_paused());
+        chip.run_frame(&mut renderer, &mut ())?;
+        chip.run_frame(&mut renderer, &mut ())?;
+        assert_eq!(chip.cycles(), per_frame);
+
+        chip.frame_advance(&mut renderer, &mut ())?;
+        assert_eq!(chip.cycles(), 2 * per_frame);
+        assert!(chip.is_paused());
+
+        chip.resume();
+        chip.run_frame(&mut renderer, &mut ())?;
+        assert_eq!(chip.cycles(), 3 * per_frame);
+        Ok(())
+    }
 
-                self.data_registers.write(register_x, data_y + data_x)?;
-                
-                self.program_counter.step(2)?;
-            },
-            OpLiteral::_8XY5 => {
+    #[test]
+    fn speed_multiplier_scales_cycles_per_frame() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::new().with_clock_hz(600);
+        chip.initialize();
+        chip.load_program_bytes(&[0x12, 0x00]);
+        let mut renderer = crate::test_support::MockRenderer::default();
 
-                // Subtract Vy from Vx. Vf is set to 0 when there's a borrow, and to 1 when there is not.
+        chip.run_frame(&mut renderer, &mut ())?;
+        let normal = chip.cycles();
 
-                let register_x = NibblePair::from(((opcode.value & 0x0F00) >> 8) as u8).low.to_hex_char();
-                let register_y = NibblePair::from(((opcode.value & 0x00F0) >> 4) as u8).low.to_hex_char();
+        chip.set_speed_multiplier(2.0);
+        chip.run_frame(&mut renderer, &mut ())?;
+        assert_eq!(chip.cycles() - normal, 2 * normal);
+        Ok(())
+    }
 
-                let data_y = self.data_registers.read(register_y)?;
-                let data_x = self.data_registers.read(register_x)?;
-                
-                if data_x as u16 > (0xFF - data_y as u16) {
-                    self.data_registers.write('f', 1)?;
-                } 
-                else {
-                    self.data_registers.write('f', 0)?;
-                }
+    #[test]
+    fn headless_render_is_deterministic() -> Result<(), Chip8Error> {
+        let rom = include_bytes!("../../programs/pong2.c8");
+        let frame = Chip8::headless_render(rom, 2_000, 3)?;
+        assert!(frame.contains('#'));
+        assert_eq!(frame.lines().count(), 32);
+        assert_eq!(Chip8::headless_render(rom, 2_000, 3)?, frame);
+        Ok(())
+    }
 
-                self.data_registers.write(register_x, data_x - data_y)?;                
-                self.program_counter.step(2)?;
-            },
-            OpLiteral::_8XY6 => {
-                // Stores the least significant bit of Vx in Vf and then shift Vx to the right by 1.
+    #[test]
+    fn from_rom_bytes_is_ready_to_run() -> Result<(), Chip8Error> {
+        let chip = Chip8::from_rom_bytes(&[0x12, 0x00])?;
+        let font = FontSet::default().0;
 
-                let register_x = NibblePair::from(((opcode.value & 0x0F00) >> 8) as u8).low.to_hex_char();
-                let data_x = self.data_registers.read(register_x)?;
-                self.data_registers.write('f', data_x & 0b1u8)?;
-                self.data_registers.write(register_x, data_x >> 1)?;
+        assert_eq!(chip.program_counter.read(), chip.entry_point());
+        assert!((0..font.len()).all(|address| chip.bus.read(address) == font[address]));
+        assert_eq!(chip.bus.read(0x200), 0x12);
+        assert!(Chip8::from_rom_bytes(&[0; crate::virtual_machine::MAX_ROM_SIZE + 1]).is_err());
+        Ok(())
+    }
 
-                self.program_counter.step(2)?;
-            },
-            OpLiteral::_8XY7 => {
+    #[test]
+    fn init_pattern_fills_what_the_program_did_not_write() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::from_rom_bytes(&[0xA4, 0x00, 0xF0, 0x65])?.with_init_pattern(InitPattern::Value(0xAA));
+        assert_eq!(chip.bus.read(0x400), 0xAA);
+        assert_eq!(chip.bus.read(0x204), 0xAA);
+        assert_eq!(chip.bus.read(0x200), 0xA4);
+        assert_eq!(chip.bus.read(0x000), FontSet::default().0[0]);
+        assert_eq!(chip.data_registers.read_idx(7)?, 0xAA);
+
+        // V0 = memory[0x400], which nothing ever wrote.
+        chip.data_registers.write_idx(0, 0)?;
+        chip.run_cycles(2)?;
+        assert_eq!(chip.data_registers.read_idx(0)?, 0xAA);
+
+        let random = |seed| Chip8::new().with_init_pattern(InitPattern::Random(seed)).bus.read(0x300);
+        let bytes: Vec<u8> = (0..8).map(random).collect();
+        assert_eq!(bytes, (0..8).map(random).collect::<Vec<u8>>());
+        assert!(bytes.iter().any(|byte| *byte != bytes[0]));
+        assert_eq!(Chip8::new().bus.read(0x300), 0);
+        Ok(())
+    }
 
-                // Subtract Vx from Vy and assign to Vx. Vf is set to 0 when there's a borrow, and to 1 when there is not.
+    #[test]
+    fn sanitizer_flags_jumps_into_uninitialized_memory() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::new().with_memory_sanitizer();
+        chip.initialize();
+        chip.load_program_bytes(&[0x60, 0x01, 0x13, 0x00]);
 
-                let register_x = NibblePair::from(((opcode.value & 0x0F00) >> 8) as u8).low.to_hex_char();
-                let register_y = NibblePair::from(((opcode.value & 0x00F0) >> 4) as u8).low.to_hex_char();
+        assert_eq!(chip.run_to_next_draw(10)?, StopReason::UninitializedFetch(0x300));
+        assert_eq!(chip.cycles(), 2);
+        Ok(())
+    }
 
-                let data_y = self.data_registers.read(register_y)?;
-                let data_x = self.data_registers.read(register_x)?;
-                
-                if data_y as u16 > (0xFF - data_x as u16) {
-                    self.data_registers.write('f', 1)?;
-                } 
-                else {
-                    self.data_registers.write('f', 0)?;
-                }
+    #[test]
+    fn frame_budget_ignores_the_clock() -> Result<(), Chip8Error> {
+        use crate::virtual_machine::MockClock;
 
-                self.data_registers.write(register_x, data_y - data_x)?;                
-                self.program_counter.step(2)?;
-            },
-            OpLiteral::_8XYE => {
-                // Stores the most significant bit of Vx in Vf and then shift Vx to the left by 1.
+        let clock = MockClock::default();
+        let mut chip = Chip8::new().with_clock_hz(600).with_clock(clock.clone());
+        chip.initialize();
+        chip.load_program_bytes(&[0x12, 0x00]);
+        let mut renderer = crate::test_support::MockRenderer::default();
 
-                let register_x = NibblePair::from(((opcode.value & 0x0F00) >> 8) as u8).low.to_hex_char();
-                let data_x = self.data_registers.read(register_x)?;
-                self.data_registers.write('f', data_x & (1u8 << 7))?;
-                self.data_registers.write(register_x, data_x << 1)?;
+        chip.run_frame(&mut renderer, &mut ())?;
+        assert_eq!(chip.cycles(), 10);
 
-                self.program_counter.step(2)?;
-            },
-            OpLiteral::_9XY0 => {
-                // Skip the next instruction if Vx does not equal Vy. (Usually the instruction is a jump to skip a code block)
+        clock.advance(Duration::from_secs(3));
+        chip.run_frame(&mut renderer, &mut ())?;
+        assert_eq!(chip.cycles(), 20);
+        Ok(())
+    }
 
-                let register_x = NibblePair::from(((opcode.value & 0x0F00) >> 8) as u8).low.to_hex_char();
-                let register_y = NibblePair::from(((opcode.value & 0x00F0) >> 4) as u8).low.to_hex_char();
+    #[test]
+    fn timers_tick_after_the_frame() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::from_rom_bytes(&[
+            0x60, 0x02, // V0 = 2
+            0xF0, 0x15, // delay timer = V0
+            0xF1, 0x07, // V1 = delay timer
+            0x12, 0x06, // loop in place
+        ])?.with_clock_hz(1000);
+
+        chip.run_frame(&mut crate::test_support::MockRenderer::default(), &mut ())?;
+        assert_eq!(chip.data_registers.read('1')?, 2);
+        assert_eq!(chip.bus.delay_timer().value(), 1);
+        Ok(())
+    }
 
-                let data_y = self.data_registers.read(register_y)?;
-                let data_x = self.data_registers.read(register_x)?;
+    #[test]
+    fn short_beeps_are_stretched_to_the_minimum() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::new().with_min_beep_ticks(4);
 
-                self.program_counter.step(2)?;
+        chip.data_registers.write('0', 1)?;
+        chip.apply_opcode(0xF018)?;
+        assert_eq!(chip.bus.sound_timer().value(), 4);
 
-                if data_x != data_y {
-                    self.program_counter.step(2)?;
-                }
-            },
-            OpLiteral::_ANNN => {
-                // Sets the address_register to NNN.
+        chip.data_registers.write('0', 9)?;
+        chip.apply_opcode(0xF018)?;
+        assert_eq!(chip.bus.sound_timer().value(), 9);
 
-                // Extract the last three quads.
-                let value = opcode.value & 0x0FFF;
-                self.address_register.write(value)?;
-                self.program_counter.step(2)?;
-            },
-            OpLiteral::_BNNN => {
-                // Jumps to the address NNN plus V0.
-                let value = opcode.value & 0x0FFF;
-                self.program_counter.write((value + self.data_registers.read('0')? as u16) & 0x0FFF as u16)?;
-            }
-            OpLiteral::_CXNN => {
-                // Sets Vx to the result of a bitwise and operation on a random number (Typically: 0 to 255) and NN.
-                let nn = (opcode.value & 0x00FF) as u8;
+        chip.data_registers.write('0', 0)?;
+        chip.apply_opcode(0xF018)?;
+        assert_eq!(chip.bus.sound_timer().value(), 0);
+        Ok(())
+    }
 
-                let register_x = NibblePair::from(((opcode.value & 0x0F00) >> 8) as u8).low.to_hex_char();
-                self.data_registers.write(register_x, nn & self.rng.gen::<u8>())?;
-                self.program_counter.step(2)?;
-            },
-            OpLiteral::_DXYN => {
-                // Draws a sprite at coordinate (Vx, Vy) that has a width of 8 pixels and height of N pixels. 
-                // Each row of 8 pixels is read as bit-coded starting from memory location I; I value does not change
-                // after the execution of this instruction. Vf is set to 1 if any screen pixels are flipped from set to unset when
-                // the sprite is drawn, and to 0 if that does not happen.
+    #[test]
+    fn run_with_timeout_stops_at_the_simulated_deadline() -> Result<(), Chip8Error> {
+        use crate::virtual_machine::MockClock;
 
-                let register_x = NibblePair::from(((opcode.value & 0x0F00) >> 8) as u8).low.to_hex_char();
-                let register_y = NibblePair::from(((opcode.value & 0x00F0) >> 4) as u8).low.to_hex_char();
+        let clock = MockClock::default();
+        let mut chip = Chip8::from_rom_bytes(&[0x12, 0x00])?.with_clock(clock.clone());
+        // Every instruction takes a simulated millisecond.
+        chip.set_post_exec_hook(move |_, _, _| clock.advance(Duration::from_millis(1)));
 
-                let data_x = self.data_registers.read(register_x)?;
-                let data_y = self.data_registers.read(register_y)?;
+        assert_eq!(chip.run_with_timeout(Duration::from_millis(100))?, StopReason::Timeout);
+        // Frames of 10 instructions run until the 100th millisecond has passed.
+        assert_eq!(chip.cycles(), 100);
+        Ok(())
+    }
 
-                let height = NibblePair::from((opcode.value & 0x000F) as u8).low;
-                
-                self.data_registers.write_idx(15, 0)?;
+    #[test]
+    fn run_with_timeout_ends_on_a_clock_that_stands_still() -> Result<(), Chip8Error> {
+        use crate::virtual_machine::MockClock;
 
-                let num_rows = height.to_u8();
+        let mut chip = Chip8::from_rom_bytes(&[0x12, 0x00])?.with_clock(MockClock::default());
+        let cycles_per_frame = chip.cycles_per_frame() as u64;
 
+        // Each frame counts as one timer period.
+        assert_eq!(chip.run_with_timeout(TIMER_PERIOD * 6)?, StopReason::Timeout);
+        assert_eq!(chip.cycles(), 6 * cycles_per_frame);
 
-                // FIXME: Rework this display logic because it is has strange
-                // out of bound access panics rn.
-                for yline in 0..num_rows as usize {
+        chip.pause();
+        assert_eq!(chip.run_with_timeout(Duration::from_millis(100))?, StopReason::Timeout);
+        assert_eq!(chip.cycles(), 6 * cycles_per_frame);
+        Ok(())
+    }
 
-                    let current_address = self.address_register.read() as usize;
-                    let pixel = self.memory[current_address + yline];
+    #[test]
+    fn timers_follow_the_mock_clock() -> Result<(), Chip8Error> {
+        use crate::virtual_machine::MockClock;
 
-                    for xline in 0..8 {
-                        if ((pixel as u16) & (0x80 >> xline)) != 0 {
-                            if self.screen[(data_x as usize + xline + ((data_y as usize + yline) * 64))] {
-                                // That pixel was already on.
-                                self.data_registers.write_idx(15, 1)?;
-                            }
-                            let current_value = self.screen[(data_x as usize + xline + ((data_y as usize + yline) * 64))];
-                            self.screen[(data_x as usize + xline + ((data_y as usize + yline) * 64))] = !current_value;
-                        }
-                    }
-                }
+        let clock = MockClock::default();
+        let mut chip = Chip8::new().with_clock(clock.clone());
+        chip.data_registers.write('0', 10)?;
 
+        chip.apply_opcode(0xF015)?;
+        clock.advance(Duration::from_secs(5) / 60);
+        chip.apply_opcode(0xF107)?;
 
-                self.should_draw = true;
-                self.program_counter.step(2)?;
-            },
-            OpLiteral::_EX9E => {
-                // Skips the next instruction if the key stored in Vx is pressed (usually the next instruction is a jump to skip a code block).
+        assert_eq!(chip.data_registers.read('1')?, 5);
+        assert_eq!(chip.bus.delay_timer().value(), 5);
+        Ok(())
+    }
 
-                let register_x = NibblePair::from(((opcode.value & 0x0F00) >> 8) as u8).low.to_hex_char();
-                let data_x = self.data_registers.read(register_x)?;
+    #[test]
+    fn register_snapshot_captures_cpu_state() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::new();
+        chip.initialize();
+        chip.load_program_bytes(&[0x63, 0x09, 0xA1, 0x23, 0x23, 0x00]);
+        for _ in 0..3 {
+            chip.step()?;
+        }
 
-                self.program_counter.step(2)?;
+        let snapshot = chip.register_snapshot();
+        assert_eq!(snapshot.data_registers.read('3')?, 0x09);
+        assert_eq!(snapshot.address_register, 0x123);
+        assert_eq!(snapshot.program_counter, 0x300);
+        assert_eq!(snapshot.stack_pointer, 1);
+        Ok(())
+    }
 
-                if self.keypad.is_pressed(data_x) {
-                    self.program_counter.step(2)?;
-                }
-            },
-            OpLiteral::_EXA1 => {
-                // Skips the next instruction if the key stored in Vx is NOT pressed (usually the next instruction is a jump to skip a code block).
+    #[test]
+    fn run_to_next_draw_stops_after_the_draw() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::new();
+        chip.initialize();
+        chip.load_program_bytes(&[
+            0x60, 0x05, // V0 = 5
+            0x61, 0x03, // V1 = 3
+            0xA0, 0x00, // I = 0
+            0xD0, 0x15, // draw
+            0x70, 0x01, // V0 += 1
+        ]);
+
+        assert_eq!(chip.run_to_next_draw(100)?, StopReason::Drew);
+        assert!(chip.should_draw);
+        assert_eq!(chip.cycles(), 4);
+        assert_eq!(chip.program_counter.read(), 0x208);
+
+        chip.should_draw = false;
+        assert_eq!(chip.run_to_next_draw(1)?, StopReason::CycleLimit);
+        assert!(!chip.should_draw);
+        Ok(())
+    }
 
-                let register_x = NibblePair::from(((opcode.value & 0x0F00) >> 8) as u8).low.to_hex_char();
-                let data_x = self.data_registers.read(register_x)?;
+    #[test]
+    fn step_out_returns_to_the_caller() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::from_rom_bytes(&[
+            0x22, 0x06, // call 0x206
+            0x60, 0x01, // V0 = 1
+            0x12, 0x04, // loop
+            0x22, 0x0C, // call 0x20C
+            0x61, 0x02, // V1 = 2
+            0x00, 0xEE, // return
+            0x62, 0x03, // V2 = 3
+            0x00, 0xEE, // return
+        ])?;
+
+        chip.step()?;
+        assert_eq!(chip.program_counter.read(), 0x206);
+        // The nested call returns on the way out, but only the outer return stops.
+        assert_eq!(chip.step_out(100)?, StopReason::Returned);
+        assert_eq!(chip.program_counter.read(), 0x202);
+        assert_eq!(chip.data_registers.read_idx(1)?, 2);
+        assert_eq!(chip.data_registers.read_idx(2)?, 3);
+        assert_eq!(chip.data_registers.read_idx(0)?, 0);
+
+        assert_eq!(chip.step_out(10)?, StopReason::CycleLimit);
+        Ok(())
+    }
 
-                self.program_counter.step(2)?;
+    #[test]
+    fn rpl_flags_round_trip() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::new();
+        for (idx, value) in [0x11, 0x22, 0x33, 0x44].into_iter().enumerate() {
+            chip.data_registers.write_idx(idx, value)?;
+        }
+        chip.apply_opcode(0xF375)?;
+        chip.data_registers = DataRegisters::default();
 
-                if !self.keypad.is_pressed(data_x) {
-                    self.program_counter.step(2)?;
-                }
-            },
-            OpLiteral::_FX07 => {
-                // Set Vx to the value of the delay timer.
-                let register_x = NibblePair::from(((opcode.value & 0x0F00) >> 8) as u8).low.to_hex_char();
-                self.data_registers.write(register_x, self.delay_timer.value())?;
-                self.program_counter.step(2)?;
+        chip.apply_opcode(0xF385)?;
+        assert_eq!(&chip.data_registers.values()[..5], &[0x11, 0x22, 0x33, 0x44, 0x00]);
 
-            },
-            OpLiteral::_FX0A => {
-                // A key press is awaited, and the stored in Vx (blocking operation, all instruction halted until next key event).
-                let register_x = NibblePair::from(((opcode.value & 0x0F00) >> 8) as u8).low.to_hex_char();
+        // X past 7 only touches the 8 flags that exist.
+        chip.apply_opcode(0xFF75)?;
+        assert_eq!(chip.rpl_flags, [0x11, 0x22, 0x33, 0x44, 0, 0, 0, 0]);
+        Ok(())
+    }
 
-                if let Some(key_event) = self.keypad.read() {
-                    self.data_registers.write(register_x, key_event & 0x0F)?;
-                }
-                self.program_counter.step(2)?;
+    #[test]
+    fn seeded_machines_agree_across_threads() {
+        const ROM: [u8; 8] = [
+            0xC0, 0xFF, // V0 = random
+            0xC1, 0x1F, // V1 = random & 0x1F
+            0xD0, 0x15, // draw the font's 0 at (V0, V1)
+            0x12, 0x00, // again
+        ];
+        let hashes: Vec<u64> = (0..4)
+            .map(|_| std::thread::spawn(|| {
+                let mut chip = Chip8::from_rom_bytes(&ROM).unwrap().with_rng_seed(42);
+                chip.run_cycles(1_000).unwrap();
+                chip.state_hash()
+            }))
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect();
+
+        assert!(hashes.iter().all(|hash| *hash == hashes[0]));
+        let mut other_seed = Chip8::from_rom_bytes(&ROM).unwrap().with_rng_seed(7);
+        other_seed.run_cycles(1_000).unwrap();
+        assert_ne!(other_seed.state_hash(), hashes[0]);
+    }
+
+    #[test]
+    fn exec_hooks_run_around_every_instruction() -> Result<(), Chip8Error> {
+        use std::sync::{Arc, Mutex};
+
+        let mut chip = Chip8::from_rom_bytes(&[0x70, 0x01, 0x12, 0x00])?;
+        let pre = Arc::new(Mutex::new(0));
+        let post = Arc::new(Mutex::new(Vec::new()));
+
+        let counter = pre.clone();
+        chip.set_pre_exec_hook(move |chip, opcode| {
+            assert_eq!(chip.fetch_opcode(), opcode.value);
+            *counter.lock().unwrap() += 1;
+        });
+        let infos = post.clone();
+        chip.set_post_exec_hook(move |_, _, info| infos.lock().unwrap().push(*info));
+
+        assert_eq!(chip.run_cycles(10)?, StopReason::CycleLimit);
+        assert_eq!(*pre.lock().unwrap(), 10);
+        assert_eq!(post.lock().unwrap().len(), 10);
+        assert_eq!(post.lock().unwrap()[0], StepInfo { pc: 0x200, next_pc: 0x202, cycles: 1 });
+        assert_eq!(post.lock().unwrap()[1], StepInfo { pc: 0x202, next_pc: 0x200, cycles: 2 });
+        Ok(())
+    }
 
+    #[test]
+    fn machine_routine_handler_runs_for_0nnn() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::new();
+        chip.initialize();
+        chip.set_machine_routine_handler(|chip, address| {
+            if address == 0x123 {
+                chip.data_registers.write_idx(3, 0x99).unwrap();
             }
-            OpLiteral::_FX15 => {
-                // Set the delay timer to Vx.
+        });
+        chip.load_program_bytes(&[
+            0x04, 0x56, // SYS 0x456
+            0x01, 0x23, // SYS 0x123
+        ]);
+
+        chip.step()?;
+        assert_eq!(chip.data_registers.read_idx(3)?, 0);
+        chip.step()?;
+        assert_eq!(chip.data_registers.read_idx(3)?, 0x99);
+        assert_eq!(chip.program_counter.read(), 0x204);
+        Ok(())
+    }
 
-                let register_x = NibblePair::from(((opcode.value & 0x0F00) >> 8) as u8).low.to_hex_char();
-                self.delay_timer.reset(self.data_registers.read(register_x)?);
+    #[test]
+    fn fx55_rewriting_the_next_instruction_runs_the_new_one() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::from_rom_bytes(&[
+            0x60, 0x62, // V0 = 0x62
+            0x61, 0x2A, // V1 = 0x2A
+            0xA2, 0x08, // I = 0x208
+            0xF1, 0x55, // store V0 and V1 over the next instruction
+            0x00, 0xE0, // clear the screen, until it becomes V2 = 0x2A
+            0x12, 0x0A, // loop
+        ])?;
+
+        // Decode the instruction that's about to be overwritten first, so a stale copy would show.
+        chip.program_counter.write(0x208)?;
+        chip.step()?;
+        assert!(chip.decode_cache[0x208].is_some());
+        chip.program_counter.write(0x200)?;
+
+        for _ in 0..4 {
+            chip.step()?;
+        }
+        assert_eq!(chip.program_counter.read(), 0x208);
+        assert_eq!(chip.fetch_opcode(), 0x622A);
 
-                self.program_counter.step(2)?;
-            },
-            OpLiteral::_FX18 => {
-                // Set the sound timer to Vx.
+        chip.step()?;
+        assert_eq!(chip.data_registers.read_idx(2)?, 0x2A);
+        assert_eq!(chip.program_counter.read(), 0x20A);
+        Ok(())
+    }
 
-                let register_x = NibblePair::from(((opcode.value & 0x0F00) >> 8) as u8).low.to_hex_char();
-                self.sound_timer.reset(self.data_registers.read(register_x)?);
+    #[test]
+    fn fuzzed_roms_fail_instead_of_panicking() -> Result<(), Chip8Error> {
+        // A jump to 0xFFF fetches its low byte from 0x000.
+        let mut chip = Chip8::from_rom_bytes(&[0x1F, 0xFF])?;
+        chip.step()?;
+        assert_eq!(chip.fetch_opcode(), u16::from_be_bytes([0, FontSet::default().0[0]]));
+
+        for rom in [[0xAF, 0xFF, 0xF0, 0x33], [0xAF, 0xFE, 0xFF, 0x55], [0xAF, 0xF8, 0xFF, 0x65]] {
+            let mut chip = Chip8::from_rom_bytes(&rom)?;
+            chip.step()?;
+            assert!(matches!(chip.step(), Err(Chip8Error::Memory(MemoryError::OutOfBounds(_)))), "{:02x?}", rom);
+            assert_eq!(chip.program_counter.read(), 0x202);
+        }
+        Ok(())
+    }
 
-                self.program_counter.step(2)?;
-            },
-            OpLiteral::_FX1E => {
-                // Adds Vx to I. Vf is unaffected.
-                let register_x = NibblePair::from(((opcode.value & 0x0F00) >> 8) as u8).low.to_hex_char();
-                let data_x = self.data_registers.read(register_x)?;
+    #[test]
+    fn poke_invalidates_decoded_instructions() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::new();
+        chip.initialize();
+        chip.load_program_bytes(&[0x60, 0x05]); // V0 = 5
 
-                self.address_register.write(
-                    (self.address_register.read() as usize + data_x as usize) as u16 
-                    & 0x0FFFu16
-                )?;
+        chip.step()?;
+        assert!(chip.decode_cache[0x200].is_some());
+        assert_eq!(chip.data_registers.read_idx(0)?, 5);
 
-                self.program_counter.step(2)?;
-            },
-            OpLiteral::_FX29 => {
-                // Sets I to the location of the sprite for the character in Vx. Characters 0-F (in hexadecimal) are represented by a 4x5 font.
+        chip.poke(0x201, 0x07)?;
+        assert_eq!(chip.bus().read(0x201), 0x07);
+        assert!(chip.decode_cache[0x200].is_none());
 
-                let register_x = NibblePair::from(((opcode.value & 0x0F00) >> 8) as u8).low.to_hex_char();
-                let data_x = self.data_registers.read(register_x)? & 0x0F;
+        chip.program_counter.write(0x200)?;
+        chip.step()?;
+        assert_eq!(chip.data_registers.read_idx(0)?, 7);
 
-                let sprite_location = 4096 - 80 + (data_x as usize * 5);
-                self.address_register.write(sprite_location as u16)?;
+        chip.set_register(0xA, 0x42)?;
+        assert_eq!(chip.data_registers.read_idx(0xA)?, 0x42);
+        assert!(chip.set_register(0x10, 0).is_err());
+        assert!(chip.poke(0x1000, 0).is_err());
+        Ok(())
+    }
 
-                self.program_counter.step(2)?;
+    #[test]
+    fn flag_wins_when_x_is_f() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::new();
+
+        // 0x80 + 0x80 carries, and the sum (0x00) is discarded.
+        chip.data_registers.write('f', 0x80)?;
+        chip.apply_opcode(0x8FF4)?;
+        assert_eq!(chip.data_registers.read('f')?, 1);
+
+        // 0x05 - 0x05 doesn't borrow.
+        chip.data_registers.write('f', 0x05)?;
+        chip.apply_opcode(0x8FF5)?;
+        assert_eq!(chip.data_registers.read('f')?, 1);
+
+        // 0x03 - 0x05 borrows, and the difference (0xFE) is discarded.
+        chip.data_registers.write('f', 0x03)?;
+        chip.data_registers.write('5', 0x05)?;
+        chip.apply_opcode(0x8F55)?;
+        assert_eq!(chip.data_registers.read('f')?, 0);
+
+        chip.data_registers.write('f', 0x05)?;
+        chip.data_registers.write('5', 0x03)?;
+        chip.apply_opcode(0x8F57)?;
+        assert_eq!(chip.data_registers.read('f')?, 0);
+
+        // 0x03 >> 1 shifts out a 1, and the shifted value (0x01) is discarded.
+        chip.data_registers.write('f', 0x02)?;
+        chip.apply_opcode(0x8FF6)?;
+        assert_eq!(chip.data_registers.read('f')?, 0);
+        chip.data_registers.write('f', 0x03)?;
+        chip.apply_opcode(0x8FF6)?;
+        assert_eq!(chip.data_registers.read('f')?, 1);
+        Ok(())
+    }
 
-            },
-            OpLiteral::_FX33 => {
-                // Store the binary-coded decimal representation of VX, with the hundreds digit in memory at location in I,
-                // the tens digit at location I + 1, and the ones digit at location I + 2.
+    #[test]
+    fn shift_left_sets_vf_to_the_msb_shifted_out() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::new();
 
-                let register_x = NibblePair::from(((opcode.value & 0x0F00) >> 8) as u8).low.to_hex_char();
-                let data_x = self.data_registers.read(register_x)? & 0x0F;
+        // 0x81 << 1 shifts out a 1; VF must hold that bit, not 0x80 or the shifted 0x02.
+        chip.data_registers.write('f', 0x81)?;
+        chip.apply_opcode(0x8FFE)?;
+        assert_eq!(chip.data_registers.read('f')?, 1);
 
-                self.memory[self.address_register.read() as usize] = ((data_x as usize) / 100) as u8;
-                self.memory[self.address_register.read() as usize + 1] = (((data_x as usize) / 10) % 10) as u8;
-                self.memory[self.address_register.read() as usize + 2] = (((data_x as usize) % 100) % 10) as u8;
-                
-                self.program_counter.step(2)?;
+        // 0x7F << 1 shifts out a 0, even though the shifted value (0xFE) is nonzero.
+        chip.data_registers.write('f', 0x7F)?;
+        chip.apply_opcode(0x8FFE)?;
+        assert_eq!(chip.data_registers.read('f')?, 0);
 
-            },
-            OpLiteral::_FX55 => {
-                // Stores from V0 to Vx (including Vx) in memory, starting at address I. The offset from I is increased by 1 for each value written, but I itself is left unmodified.
+        chip.data_registers.write('3', 0xC0)?;
+        chip.apply_opcode(0x833E)?;
+        assert_eq!(chip.data_registers.read('3')?, 0x80);
+        assert_eq!(chip.data_registers.read('f')?, 1);
+        Ok(())
+    }
 
-                let register_x = NibblePair::from(((opcode.value & 0x0F00) >> 8) as u8).low.to_hex_char();
-                let register_breakpoint = usize::from_str_radix(&String::from(register_x), 16)?;
-                let mut current_address = self.address_register.read() as usize;
+    #[test]
+    fn programs_start_at_the_entry_point() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::new().with_entry_point(0x600);
+        chip.initialize();
+        chip.load_program_bytes(&[0x60, 0x2A]); // V0 = 0x2A
 
-                for register_idx in 0..=register_breakpoint {
+        assert_eq!(chip.program_counter.read(), 0x600);
+        assert_eq!(chip.fetch_opcode(), 0x602A);
+        assert_eq!(chip.bus().read(0x200), 0);
 
-                    let to_store = self.data_registers.read_idx(register_idx)?;
-                    self.memory[current_address] = to_store;
-                    current_address += 1;
+        chip.step()?;
+        assert_eq!(chip.data_registers.read_idx(0)?, 0x2A);
+        Ok(())
+    }
 
-                }
-                self.program_counter.step(2)?;
-            },
-            OpLiteral::_FX65 => {
-                // Fills from V0 to Vx (including Vx) with values from memory, starting at address I. The offset from I is increased by 1 for each value read, but I itself is left unmodified.
+    #[test]
+    fn programs_past_the_end_of_memory_are_truncated() {
+        let mut chip = Chip8::new().with_entry_point(0x600);
+        chip.initialize();
+        chip.load_program_bytes(&[0xAB; 3072]);
 
-                let register_x = NibblePair::from(((opcode.value & 0x0F00) >> 8) as u8).low.to_hex_char();
-                let register_breakpoint = usize::from_str_radix(&String::from(register_x), 16)?;
-                let mut current_address = self.address_register.read() as usize;
+        assert_eq!(chip.program_len, MEMORY_SIZE - 0x600);
+        assert_eq!(chip.bus().read(MEMORY_SIZE - 1), 0xAB);
+    }
 
-                for register_idx in 0..=register_breakpoint {
+    #[test]
+    fn breakpoints_stop_before_the_instruction() -> Result<(), Chip8Error> {
+        let mut renderer = crate::test_support::MockRenderer::default();
+        let mut chip = Chip8::new();
+        chip.initialize();
+        chip.load_program_bytes(&[
+            0x60, 0x01, // V0 = 1
+            0x61, 0x02, // V1 = 2
+            0x62, 0x03, // V2 = 3
+            0x00, 0xFD, // exit
+        ]);
+        chip.add_breakpoint(0x204);
+
+        assert_eq!(chip.run_frame(&mut renderer, &mut ())?, Some(StopReason::Breakpoint(0x204)));
+        assert_eq!(chip.program_counter.read(), 0x204);
+        assert_eq!(chip.cycles(), 2);
+
+        // Resuming steps over the breakpoint we stopped at.
+        assert_eq!(chip.run_frame(&mut renderer, &mut ())?, Some(StopReason::Halted));
+        assert!(chip.is_halted());
+        assert_eq!(chip.data_registers.read_idx(2)?, 3);
+        assert_eq!(chip.run_frame(&mut renderer, &mut ())?, Some(StopReason::Halted));
+        assert_eq!(chip.cycles(), 4);
+        Ok(())
+    }
 
-                    let to_fill = self.memory[current_address];
-                    self.data_registers.write_idx(register_idx, to_fill)?;
+    #[test]
+    fn watchpoints_report_changed_memory() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::new();
+        chip.initialize();
+        chip.load_program_bytes(&[
+            0x60, 0x2A, // V0 = 0x2A
+            0xA3, 0x00, // I = 0x300
+            0xF0, 0x55, // store V0 at I
+            0x12, 0x06, // loop
+        ]);
+        chip.add_watchpoint(0x300);
+
+        assert_eq!(
+            chip.run_to_next_draw(10)?,
+            StopReason::Watchpoint { address: 0x300, old: 0, new: 0x2A }
+        );
+        assert_eq!(chip.program_counter.read(), 0x206);
+        assert_eq!(chip.run_to_next_draw(10)?, StopReason::CycleLimit);
+        Ok(())
+    }
 
-                    current_address += 1;
-                }
-                self.program_counter.step(2)?;
-            }
+    #[test]
+    fn frame_diff_lists_flipped_pixels() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::new();
+        chip.bus_mut().screen_mut()[3 + 2 * 64] = true;
+        chip.bus_mut().screen_mut()[10] = true;
+        let prev = chip.bus().screen().clone();
+
+        chip.bus_mut().screen_mut()[10] = false;
+        chip.bus_mut().screen_mut()[63 + 31 * 64] = true;
+        chip.bus_mut().screen_mut()[0] = true;
+
+        let diff = chip.frame_diff(&prev);
+        assert_eq!(diff.turned_on, vec![(0, 0), (63, 31)]);
+        assert_eq!(diff.turned_off, vec![(10, 0)]);
+        assert_eq!(diff.count(), 3);
+        assert!(chip.frame_diff(chip.bus().screen()).is_empty());
+
+        // Across a resolution switch, whatever is lit now counts as turned on.
+        let prev = chip.bus().screen().clone();
+        chip.apply_opcode(0x00FF)?;
+        chip.bus_mut().screen_mut()[127 + 63 * 128] = true;
+        let diff = chip.frame_diff(&prev);
+        assert_eq!(diff.turned_on, vec![(127, 63)]);
+        assert!(diff.turned_off.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn timers_keep_running_while_fx0a_waits() -> Result<(), Chip8Error> {
+        use crate::virtual_machine::ProgrammaticInput;
+
+        let mut renderer = crate::test_support::MockRenderer::default();
+        let mut input = ProgrammaticInput::default();
+        let mut chip = Chip8::new();
+        chip.initialize();
+        chip.load_program_bytes(&[
+            0x60, 0x0A, // V0 = 10
+            0xF0, 0x15, // delay timer = V0
+            0xF1, 0x0A, // wait for a key into V1
+        ]);
+
+        for _ in 0..3 {
+            chip.service_input(&mut input);
+            chip.run_frame(&mut renderer, &mut ())?;
         }
+        assert_eq!(chip.program_counter.read(), 0x204);
+        assert_eq!(chip.bus().delay_timer().value(), 7);
+
+        input.push(KeyEvent::Pressed(0xB));
+        chip.service_input(&mut input);
+        chip.step()?;
+        assert_eq!(chip.program_counter.read(), 0x206);
+        assert_eq!(chip.data_registers.read_idx(1)?, 0xB);
+        Ok(())
+    }
 
+    #[test]
+    fn zero_clock_fails_validation() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::from_rom_bytes(&[0x12, 0x00])?.with_clock_hz(0);
+        assert!(matches!(chip.validate_timing(), Err(Chip8Error::Timing(TimingError::ZeroClock))));
+        assert!(matches!(chip.run_frame(&mut (), &mut ()), Err(Chip8Error::Timing(TimingError::ZeroClock))));
+        assert_eq!(chip.cycles(), 0);
+
+        let mut chip = chip.with_clock_hz(600);
+        chip.set_speed_multiplier(0.01);
+        assert!(matches!(chip.validate_timing(), Err(Chip8Error::Timing(TimingError::NoCyclesPerFrame { clock_hz: 600, .. }))));
+        chip.set_speed_multiplier(1.0);
+        assert!(chip.validate_timing().is_ok());
         Ok(())
     }
 
-    fn step(&mut self) -> Result<(), Box<dyn std::error::Error>>{
-        let opcode = self.fetch_opcode();
-        println!("program counter: {:04x}", self.program_counter.read() - 0x200);
-        println!("will apply opcode: {:#?}", OpCode::try_from(opcode)?);
-        self.apply_opcode(opcode)?;
-        println!("after application: program counter: {:04x}", self.program_counter.read() - 0x200);
+    #[test]
+    fn run_frame_stops_at_the_cycle_cap() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::from_rom_bytes(&[
+            0x70, 0x01, // V0 += 1
+            0x12, 0x00, // again
+        ])?.with_max_cycles_per_frame(7);
+        chip.set_speed_multiplier(1_000.0);
 
-        if self.delay_timer.value() > 0 {
-            self.delay_timer.tick();
-        }
-        if self.sound_timer.value() > 0 {
-            if self.sound_timer.value() == 1 {
-                println!(
-                    "BEEP!\n"
-                );
-            }
-            self.sound_timer.tick();
-        }
+        assert_eq!(chip.run_frame(&mut (), &mut ())?, Some(StopReason::CycleLimit));
+        assert_eq!(chip.cycles(), 7);
+
+        let mut uncapped = Chip8::from_rom_bytes(&[0x12, 0x00])?;
+        assert_eq!(uncapped.run_frame(&mut (), &mut ())?, None);
+        assert_eq!(uncapped.cycles(), 10);
         Ok(())
+    }
 
+    #[test]
+    fn dxy0_draws_nothing_in_lores() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::from_rom_bytes(&[
+            0x6F, 0x01, // VF = 1
+            0xA2, 0x00, // I = the program itself, which isn't blank
+            0xD0, 0x00, // draw zero rows
+        ])?;
+        chip.run_cycles(2)?;
+        chip.should_draw = false;
+        chip.step()?;
+
+        assert_eq!(chip.data_registers.read('f')?, 0);
+        assert_eq!(chip.bus().screen(), &Screen::default());
+        assert!(!chip.should_draw);
+        assert_eq!(chip.program_counter.read(), 0x206);
+        Ok(())
     }
-    pub fn start(&mut self) -> Result<(), Box<dyn std::error::Error>>{
-        loop {
-            self.step()?;
 
-            if self.should_draw {
-                println!("{}", self.screen);
-                self.should_draw = false;
+    #[test]
+    fn dxy0_draws_16x16_sprites_in_hires() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::new();
+        chip.initialize();
+        chip.load_program_bytes(&[
+            0x00, 0xFF, // hires
+            0x60, 0x64, // V0 = 100
+            0x61, 0x28, // V1 = 40
+            0xA3, 0x00, // I = 0x300
+            0xD0, 0x10, // draw 16x16
+        ]);
+        // A 16x16 frame: solid top and bottom rows, and the left and right columns.
+        let mut sprite = [0x80, 0x01].repeat(16);
+        sprite[..2].copy_from_slice(&[0xFF, 0xFF]);
+        sprite[30..].copy_from_slice(&[0xFF, 0xFF]);
+        for (offset, byte) in sprite.iter().enumerate() {
+            chip.poke(0x300 + offset as u16, *byte)?;
+        }
+        for _ in 0..5 {
+            chip.step()?;
+        }
+
+        let screen = chip.bus().screen();
+        assert!(screen.is_hires());
+        assert_eq!(screen.len(), 128 * 64);
+        for y in 0..64 {
+            for x in 0..128 {
+                let inside = (100..116).contains(&x) && (40..56).contains(&y);
+                let border = x == 100 || x == 115 || y == 40 || y == 55;
+                assert_eq!(screen[x + y * 128], inside && border, "pixel ({}, {})", x, y);
             }
         }
+        assert_eq!(chip.data_registers.read('f')?, 0);
+
+        chip.apply_opcode(0x00FE)?;
+        assert_eq!(chip.bus().screen().len(), 64 * 32);
+        Ok(())
     }
-}
\ No newline at end of file
+}
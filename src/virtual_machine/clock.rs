@@ -0,0 +1,55 @@
+use core::time::Duration;
+#[cfg(target_has_atomic = "64")]
+use core::sync::atomic::{AtomicU64, Ordering};
+
+#[cfg(target_has_atomic = "64")]
+use alloc::sync::Arc;
+
+
+/// How long one tick of the 60 Hz delay and sound timers lasts.
+pub const TIMER_PERIOD: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// A source of elapsed time used to drive the 60 Hz timers.
+pub trait Clock: core::fmt::Debug {
+    /// Time elapsed since some fixed starting point. Must never go backwards.
+    fn now(&self) -> Duration;
+}
+
+/// Wall-clock time.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct SystemClock(std::time::Instant);
+
+#[cfg(feature = "std")]
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self(std::time::Instant::now())
+    }
+}
+
+#[cfg(feature = "std")]
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.0.elapsed()
+    }
+}
+
+/// A clock that only moves when told to. Clones share the same time,
+/// so a test can keep one handle and give the other to the machine.
+#[cfg(target_has_atomic = "64")]
+#[derive(Debug, Clone, Default)]
+pub struct MockClock(Arc<AtomicU64>);
+
+#[cfg(target_has_atomic = "64")]
+impl MockClock {
+    pub fn advance(&self, by: Duration) {
+        self.0.fetch_add(by.as_nanos() as u64, Ordering::SeqCst);
+    }
+}
+
+#[cfg(target_has_atomic = "64")]
+impl Clock for MockClock {
+    fn now(&self) -> Duration {
+        Duration::from_nanos(self.0.load(Ordering::SeqCst))
+    }
+}
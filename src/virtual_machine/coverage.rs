@@ -0,0 +1,58 @@
+use alloc::vec::Vec;
+
+use super::{Bus, Chip8, OpLiteral};
+
+
+/// Which of the base CHIP-8 instructions a machine has executed, see `Chip8::opcode_coverage`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoverageReport {
+    /// Every literal in `OpLiteral::BASE`, and whether it ran at least once.
+    pub opcodes: Vec<(OpLiteral, bool)>,
+}
+
+impl CoverageReport {
+    pub fn is_covered(&self, literal: OpLiteral) -> bool {
+        self.opcodes.iter().any(|(covered, ran)| *covered == literal && *ran)
+    }
+
+    /// The share of base instructions that ran, from 0.0 to 1.0.
+    pub fn fraction(&self) -> f32 {
+        let covered = self.opcodes.iter().filter(|(_, ran)| *ran).count();
+        covered as f32 / self.opcodes.len() as f32
+    }
+}
+
+impl<B: Bus> Chip8<B> {
+    /// Which base instructions have executed since the machine was last initialized.
+    pub fn opcode_coverage(&self) -> CoverageReport {
+        CoverageReport {
+            opcodes: OpLiteral::BASE
+                .into_iter()
+                .map(|literal| (literal, self.executed[literal as usize]))
+                .collect(),
+        }
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::virtual_machine::Chip8Error;
+
+    #[test]
+    fn only_executed_opcodes_are_covered() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::from_rom_bytes(&[
+            0x60, 0x01, // V0 = 1
+            0x12, 0x00, // jump back
+        ])?;
+        chip.run_cycles(4)?;
+
+        let report = chip.opcode_coverage();
+        assert!(report.is_covered(OpLiteral::_6XNN));
+        assert!(report.is_covered(OpLiteral::_1NNN));
+        assert_eq!(report.opcodes.iter().filter(|(_, ran)| *ran).count(), 2);
+        assert_eq!(report.fraction(), 2.0 / 35.0);
+        Ok(())
+    }
+}
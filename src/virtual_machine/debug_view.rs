@@ -0,0 +1,134 @@
+use alloc::{format, string::String, vec::Vec};
+
+use super::{Bus, Chip8, OpCode, MEMORY_SIZE};
+
+
+/// One decoded instruction in a disassembly listing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisassemblyLine {
+    pub address: u16,
+    pub word: u16,
+    /// The mnemonic, or `DW 0x....` for words that don't decode.
+    pub text: String,
+    pub is_pc: bool,
+    pub is_breakpoint: bool,
+}
+
+/// Everything a debugger front-end shows, captured from a machine at one point in time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DebugView {
+    pub registers: [u8; 16],
+    pub address_register: u16,
+    pub program_counter: u16,
+    pub stack_pointer: u16,
+    pub call_stack: Vec<u16>,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    /// Instructions around the program counter, in address order.
+    pub disassembly: Vec<DisassemblyLine>,
+    /// Rows of 8 bytes starting at the requested address.
+    pub memory: Vec<(u16, [u8; 8])>,
+    /// The screen as text, see `Screen::to_ascii`.
+    pub screen: String,
+}
+
+impl<B: Bus> Chip8<B> {
+    /// Captures the machine for display. The disassembly covers `context` instructions on either
+    /// side of the program counter, and the memory pane `memory_rows` rows from `memory_start`.
+    pub fn debug_view(&self, context: u16, memory_start: u16, memory_rows: usize) -> DebugView {
+        let pc = self.program_counter.read();
+        let first = pc.saturating_sub(2 * context);
+        let last = pc.saturating_add(2 * context).min(MEMORY_SIZE as u16 - 2);
+
+        let disassembly = (first..=last)
+            .step_by(2)
+            .map(|address| {
+                let word = u16::from_be_bytes([self.bus.read(address as usize), self.bus.read(address as usize + 1)]);
+                let text = match OpCode::try_from(word) {
+                    Ok(opcode) => format!("{}", opcode),
+                    Err(_) => format!("DW {:#06x}", word),
+                };
+                DisassemblyLine {
+                    address,
+                    word,
+                    text,
+                    is_pc: address == pc,
+                    is_breakpoint: self.breakpoints.contains(&address),
+                }
+            })
+            .collect();
+
+        let memory = (0..memory_rows)
+            .map(|row| memory_start as usize + row * 8)
+            .take_while(|address| address + 8 <= MEMORY_SIZE)
+            .map(|address| {
+                let mut bytes = [0u8; 8];
+                for (offset, byte) in bytes.iter_mut().enumerate() {
+                    *byte = self.bus.read(address + offset);
+                }
+                (address as u16, bytes)
+            })
+            .collect();
+
+        DebugView {
+            registers: self.data_registers.values(),
+            address_register: self.address_register.read(),
+            program_counter: pc,
+            stack_pointer: self.stack_pointer,
+            call_stack: self.call_stack().to_vec(),
+            delay_timer: self.bus.delay_timer().value(),
+            sound_timer: self.bus.sound_timer().value(),
+            disassembly,
+            memory,
+            screen: self.bus.screen().to_ascii('#', ' '),
+        }
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::virtual_machine::Chip8Error;
+
+    #[test]
+    fn debug_view_from_a_running_machine() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::new();
+        chip.initialize();
+        chip.load_program_bytes(&[
+            0x60, 0x2A, // V0 = 0x2A
+            0xA2, 0x00, // I = 0x200
+            0xD0, 0x01, // draw
+            0xFF, 0xFF, // not an instruction
+        ]);
+        chip.add_breakpoint(0x206);
+        chip.step()?;
+        chip.step()?;
+
+        let view = chip.debug_view(2, 0x200, 2);
+        assert_eq!(view.registers[0], 0x2A);
+        assert_eq!(view.address_register, 0x200);
+        assert_eq!(view.program_counter, 0x204);
+
+        let lines: Vec<(u16, &str)> = view.disassembly.iter().map(|line| (line.address, line.text.as_str())).collect();
+        assert_eq!(
+            lines,
+            vec![
+                (0x200, "LD V0, 0x2a"),
+                (0x202, "LD I, 0x200"),
+                (0x204, "DRW V0, V0, 1"),
+                (0x206, "DW 0xffff"),
+                (0x208, "SYS 0x000"),
+            ]
+        );
+        assert!(view.disassembly[2].is_pc && !view.disassembly[1].is_pc);
+        assert!(view.disassembly[3].is_breakpoint);
+
+        assert_eq!(view.memory, vec![
+            (0x200, [0x60, 0x2A, 0xA2, 0x00, 0xD0, 0x01, 0xFF, 0xFF]),
+            (0x208, [0; 8]),
+        ]);
+        assert_eq!(view.screen.lines().count(), 32);
+        Ok(())
+    }
+}
@@ -0,0 +1,145 @@
+use alloc::vec::Vec;
+
+use super::{Bus, Chip8, OpCode, OpLiteral, Quirks, MAX_ROM_SIZE, MEMORY_SIZE};
+
+
+/// How many instructions `diagnose_quirks` runs under each setting.
+pub const DIAGNOSE_CYCLES: usize = 10_000;
+
+/// How a ROM fared when run for a while under one set of quirks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuirkRun {
+    /// Nothing went wrong, or the ROM settled into waiting for a key or looping in place.
+    Clean,
+    /// The instruction at `pc` doesn't decode.
+    IllegalOpcode { pc: u16 },
+    /// The `DXYN` at `pc` reads its sprite past the end of memory.
+    OutOfRangeDraw { pc: u16 },
+    /// The instruction at `pc` would overflow the stack or access memory that doesn't exist.
+    Fault { pc: u16 },
+}
+
+/// Which quirks a ROM seems to need, see `Chip8::diagnose_quirks`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuirksHints {
+    /// The ROM under `Quirks::default()`.
+    pub default_run: QuirkRun,
+    /// The ROM with each quirk turned on by itself, by field name.
+    pub runs: Vec<(&'static str, QuirkRun)>,
+}
+
+impl QuirksHints {
+    /// Every quirk that on its own fixed a ROM which misbehaved with the defaults.
+    pub fn suggested(&self) -> Quirks {
+        if self.default_run == QuirkRun::Clean {
+            return Quirks::default();
+        }
+        Quirks::each()
+            .into_iter()
+            .zip(self.runs.iter())
+            .filter(|(_, (_, run))| *run == QuirkRun::Clean)
+            .fold(Quirks::default(), |suggested, ((_, quirk), _)| suggested.union(quirk))
+    }
+}
+
+impl Chip8 {
+    /// Runs `bytes` for up to `DIAGNOSE_CYCLES` instructions, first with the default quirks and
+    /// then with each quirk on by itself, and reports which settings avoid illegal opcodes and
+    /// out-of-range draws. This is a heuristic: a clean run doesn't mean the quirks are right.
+    /// Bytes past the `MAX_ROM_SIZE` that fit in memory are ignored.
+    pub fn diagnose_quirks(bytes: &[u8]) -> QuirksHints {
+        let bytes = &bytes[..bytes.len().min(MAX_ROM_SIZE)];
+        QuirksHints {
+            default_run: Self::trial_run(bytes, Quirks::default()),
+            runs: Quirks::each()
+                .into_iter()
+                .map(|(name, quirks)| (name, Self::trial_run(bytes, quirks)))
+                .collect(),
+        }
+    }
+
+    fn trial_run(bytes: &[u8], quirks: Quirks) -> QuirkRun {
+        let mut chip = Chip8::new().with_quirks(quirks);
+        chip.initialize();
+        chip.load_program_bytes(bytes);
+
+        for _ in 0..DIAGNOSE_CYCLES {
+            let pc = chip.program_counter.read();
+            if pc as usize + 1 >= MEMORY_SIZE {
+                return QuirkRun::Fault { pc };
+            }
+            let Ok(opcode) = OpCode::try_from(chip.fetch_opcode()) else {
+                return QuirkRun::IllegalOpcode { pc };
+            };
+            let (x, i) = (opcode.x() as usize, chip.address_register.read() as usize);
+
+            match opcode.literal {
+                // Nothing more to learn without input, or from a program that's done.
+                OpLiteral::_FX0A | OpLiteral::_00FD => break,
+                OpLiteral::_1NNN if opcode.nnn() == pc => break,
+                OpLiteral::_DXYN => {
+                    // Starting coordinates wrap, so only the sprite read itself can go out of range.
+                    let len = if opcode.n() == 0 && chip.bus.screen().is_hires() { 32 } else { opcode.n() as usize };
+                    if i + len > MEMORY_SIZE {
+                        return QuirkRun::OutOfRangeDraw { pc };
+                    }
+                },
+                OpLiteral::_2NNN if chip.stack_pointer as usize >= chip.stack.len() => return QuirkRun::Fault { pc },
+                OpLiteral::_00EE if chip.stack_pointer == 0 => return QuirkRun::Fault { pc },
+                OpLiteral::_FX33 if i + 3 > MEMORY_SIZE => return QuirkRun::Fault { pc },
+                OpLiteral::_FX55 | OpLiteral::_FX65 if i + x + 1 > MEMORY_SIZE => return QuirkRun::Fault { pc },
+                _ => {}
+            }
+            if chip.step().is_err() {
+                return QuirkRun::Fault { pc };
+            }
+        }
+        QuirkRun::Clean
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::virtual_machine::BundledRoms;
+
+    #[test]
+    fn suggests_index_increment_for_sequential_fx55_writes() {
+        // Builds a `JP 0x28C` at 0x210 one byte at a time, relying on FX55 advancing I.
+        // Without the quirk the second store overwrites the first, leaving `8CFF`.
+        let mut rom = vec![0u8; 0x8E];
+        rom[..12].copy_from_slice(&[
+            0xA2, 0x10, // I = 0x210
+            0x60, 0x12, // V0 = 0x12
+            0xF0, 0x55, // [I] = V0
+            0x60, 0x8C, // V0 = 0x8C
+            0xF0, 0x55, // [I] = V0
+            0x12, 0x10, // jump to what was just written
+        ]);
+        rom[0x10..0x12].copy_from_slice(&[0xFF, 0xFF]);
+        rom[0x8C..].copy_from_slice(&[0x12, 0x8C]); // loop in place
+
+        let hints = Chip8::diagnose_quirks(&rom);
+        assert_eq!(hints.default_run, QuirkRun::IllegalOpcode { pc: 0x210 });
+        assert!(hints.runs.contains(&("index_increment", QuirkRun::Clean)));
+        assert_eq!(hints.suggested(), Quirks { index_increment: true, ..Quirks::default() });
+    }
+
+    #[test]
+    fn broken_roms_are_diagnosed_without_panicking() {
+        assert_eq!(Chip8::diagnose_quirks(&[0x1F, 0xFF]).default_run, QuirkRun::Fault { pc: 0xFFF });
+
+        let mut oversized = vec![0u8; MEMORY_SIZE];
+        oversized[..2].copy_from_slice(&[0x12, 0x00]);
+        assert_eq!(Chip8::diagnose_quirks(&oversized).default_run, QuirkRun::Clean);
+    }
+
+    #[test]
+    fn bundled_roms_run_cleanly() {
+        for name in BundledRoms::names() {
+            let hints = Chip8::diagnose_quirks(BundledRoms::get(name).unwrap());
+            assert_eq!(hints.default_run, QuirkRun::Clean, "{}", name);
+        }
+    }
+}
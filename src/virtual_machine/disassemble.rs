@@ -0,0 +1,203 @@
+use alloc::{collections::BTreeSet, format, string::String, vec, vec::Vec};
+use core::fmt;
+
+use super::{Chip8, OpCode, OpLiteral, MEMORY_SIZE};
+
+
+/// How many bytes of data go on one `.db` line at most.
+const DATA_BYTES_PER_LINE: usize = 8;
+
+/// One line of a listing from `Chip8::disassemble_smart`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListingLine {
+    /// An instruction that can be reached from the entry point.
+    Code { address: u16, word: u16, text: String },
+    /// Bytes no reachable instruction covers, like sprites and lookup tables.
+    Data { address: u16, bytes: Vec<u8> },
+}
+
+impl fmt::Display for ListingLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Code { address, word, text } => write!(f, "{:#05x}  {:04x}  {}", address, word, text),
+            Self::Data { address, bytes } => {
+                let bytes: Vec<String> = bytes.iter().map(|byte| format!("{:#04x}", byte)).collect();
+                write!(f, "{:#05x}        .db {}", address, bytes.join(", "))
+            },
+        }
+    }
+}
+
+/// Which subroutines call which, see `Chip8::call_graph`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallGraph {
+    /// The entry point, which is treated as a subroutine nobody calls.
+    pub entry: u16,
+    /// `(caller, callee)` pairs of subroutine entry addresses, sorted.
+    pub edges: Vec<(u16, u16)>,
+}
+
+impl CallGraph {
+    /// Renders the graph in Graphviz's DOT language.
+    pub fn to_dot(&self) -> String {
+        let mut dot = format!("digraph calls {{\n    \"{:#05x}\";\n", self.entry);
+        for (caller, callee) in &self.edges {
+            dot.push_str(&format!("    \"{:#05x}\" -> \"{:#05x}\";\n", caller, callee));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Where execution can go after the instruction at `address`.
+fn successors(address: u16, opcode: &OpCode) -> Vec<u16> {
+    let next = address.wrapping_add(2);
+    match opcode.literal {
+        OpLiteral::_00EE | OpLiteral::_00FD => vec![],
+        OpLiteral::_1NNN => vec![opcode.nnn()],
+        OpLiteral::_2NNN => vec![opcode.nnn(), next],
+        // The real target depends on a register; the start of the jump table is a fair guess.
+        OpLiteral::_BNNN => vec![opcode.nnn()],
+        OpLiteral::_3XNN | OpLiteral::_4XNN | OpLiteral::_5XY0 | OpLiteral::_9XY0
+        | OpLiteral::_EX9E | OpLiteral::_EXA1 => vec![next, next.wrapping_add(2)],
+        _ => vec![next],
+    }
+}
+
+/// The part of a ROM loaded at `base` that fits in memory, so every offset into it has an
+/// address.
+fn in_memory(bytes: &[u8], base: u16) -> &[u8] {
+    &bytes[..bytes.len().min(MEMORY_SIZE.saturating_sub(base as usize))]
+}
+
+/// Decodes `bytes` at `address`, if that's inside the ROM and a valid instruction.
+fn decode_at(bytes: &[u8], base: u16, address: u16) -> Option<OpCode> {
+    let offset = address.checked_sub(base)? as usize;
+    if offset + 1 >= bytes.len() {
+        return None;
+    }
+    OpCode::try_from(u16::from_be_bytes([bytes[offset], bytes[offset + 1]])).ok()
+}
+
+impl Chip8 {
+    /// Statically finds the subroutines of a ROM loaded at `base`, starting from its first
+    /// instruction. Each subroutine is followed through its jumps and skips, and every `2NNN`
+    /// in it becomes an edge to the subroutine at `NNN`. Bytes past the end of memory are ignored.
+    pub fn call_graph(bytes: &[u8], base: u16) -> CallGraph {
+        let bytes = in_memory(bytes, base);
+        let mut edges = BTreeSet::new();
+        let mut subroutines = BTreeSet::from([base]);
+        let mut pending_subroutines = vec![base];
+
+        while let Some(subroutine) = pending_subroutines.pop() {
+            let mut seen = BTreeSet::new();
+            let mut pending = vec![subroutine];
+            while let Some(address) = pending.pop() {
+                if !seen.insert(address) {
+                    continue;
+                }
+                let Some(opcode) = decode_at(bytes, base, address) else {
+                    continue;
+                };
+                if opcode.literal == OpLiteral::_2NNN {
+                    edges.insert((subroutine, opcode.nnn()));
+                    if subroutines.insert(opcode.nnn()) {
+                        pending_subroutines.push(opcode.nnn());
+                    }
+                    pending.push(address.wrapping_add(2));
+                } else {
+                    pending.extend(successors(address, &opcode));
+                }
+            }
+        }
+
+        CallGraph { entry: base, edges: edges.into_iter().collect() }
+    }
+
+    /// Disassembles a ROM loaded at `base` by following jumps, calls and skips from its first
+    /// instruction. Anything never reached is listed as `.db` data instead of being decoded.
+    /// Bytes past the end of memory are ignored.
+    pub fn disassemble_smart(bytes: &[u8], base: u16) -> Vec<ListingLine> {
+        let bytes = in_memory(bytes, base);
+        let mut instructions: Vec<Option<OpCode>> = vec![None; bytes.len()];
+        let mut pending = vec![base];
+        while let Some(address) = pending.pop() {
+            let offset = address.wrapping_sub(base) as usize;
+            if instructions.get(offset).is_some_and(Option::is_some) {
+                continue;
+            }
+            let Some(opcode) = decode_at(bytes, base, address) else {
+                continue;
+            };
+            instructions[offset] = Some(opcode);
+            pending.extend(successors(address, &opcode));
+        }
+
+        let mut listing = Vec::new();
+        let mut offset = 0;
+        while offset < bytes.len() {
+            let address = base + offset as u16;
+            if let Some(opcode) = instructions[offset] {
+                listing.push(ListingLine::Code { address, word: opcode.value, text: format!("{}", opcode) });
+                offset += 2;
+                continue;
+            }
+            let end = (offset..bytes.len())
+                .take(DATA_BYTES_PER_LINE)
+                .find(|offset| instructions[*offset].is_some())
+                .unwrap_or((offset + DATA_BYTES_PER_LINE).min(bytes.len()));
+            listing.push(ListingLine::Data { address, bytes: bytes[offset..end].to_vec() });
+            offset = end;
+        }
+        listing
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn unreachable_sprite_table_is_data() {
+        let rom = [
+            0xA2, 0x06, // I = the sprite
+            0xD0, 0x15, // draw it
+            0x12, 0x02, // and again
+            0x60, 0x60, 0x60, 0x60, 0x60, // a sprite that happens to decode as `LD V0, 0x60`
+        ];
+
+        let listing = Chip8::disassemble_smart(&rom, 0x200);
+        assert_eq!(listing.len(), 4);
+        assert!(matches!(listing[..3], [ListingLine::Code { .. }, ListingLine::Code { .. }, ListingLine::Code { .. }]));
+        assert_eq!(listing[3], ListingLine::Data { address: 0x206, bytes: vec![0x60; 5] });
+        assert_eq!(listing[3].to_string(), "0x206        .db 0x60, 0x60, 0x60, 0x60, 0x60");
+    }
+
+    #[test]
+    fn one_subroutine_makes_one_call_edge() {
+        let rom = [
+            0x22, 0x04, // call 0x204
+            0x12, 0x02, // loop in place
+            0x60, 0x01, // V0 = 1
+            0x00, 0xEE, // return
+        ];
+
+        let graph = Chip8::call_graph(&rom, 0x200);
+        assert_eq!(graph.edges, vec![(0x200, 0x204)]);
+        assert_eq!(graph.to_dot(), "digraph calls {\n    \"0x200\";\n    \"0x200\" -> \"0x204\";\n}\n");
+    }
+
+    #[test]
+    fn bytes_past_the_end_of_memory_are_ignored() {
+        let rom = vec![0x12; 70_000];
+
+        let listing = Chip8::disassemble_smart(&rom, 0x200);
+        let ListingLine::Data { address, bytes } = listing.last().unwrap() else {
+            panic!("expected data at the end, got {:?}", listing.last());
+        };
+        assert_eq!(*address as usize + bytes.len(), MEMORY_SIZE);
+
+        assert_eq!(Chip8::call_graph(&rom, 0x200).edges, vec![]);
+    }
+}
@@ -1,3 +1,6 @@
+/// Where `initialize` loads the font, and so where `FX29` points I.
+pub const FONT_BASE: u16 = 0x000;
+
 pub struct FontSet(pub [u8; 16 * 5]);
 
 impl Default for FontSet {
@@ -24,6 +27,7 @@ impl Default for FontSet {
     }
 }
 
+#[cfg(feature = "std")]
 impl FontSet {
     pub fn show_digit(&self, digit: usize) -> Result<String, Box<dyn std::error::Error>> {
         if digit >= 16 {
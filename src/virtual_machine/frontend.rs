@@ -0,0 +1,264 @@
+use alloc::{collections::VecDeque, vec::Vec};
+
+use super::Screen;
+#[cfg(feature = "std")]
+use crate::data_structures::HexKeyMap;
+
+
+/// Presents the display. Called whenever a frame changed the screen.
+pub trait Renderer {
+    fn render(&mut self, screen: &Screen);
+}
+
+/// No display, e.g. for headless runs that only look at the screen at the end.
+impl Renderer for () {
+    fn render(&mut self, _: &Screen) {}
+}
+
+/// Drives the buzzer. The tone plays for as long as the sound timer is non-zero.
+pub trait Audio {
+    fn start_tone(&mut self);
+    fn stop_tone(&mut self);
+}
+
+/// No sound at all.
+impl Audio for () {
+    fn start_tone(&mut self) {}
+    fn stop_tone(&mut self) {}
+}
+
+/// Input from the host, already mapped onto the CHIP-8 keypad.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyEvent {
+    Pressed(u8),
+    Released(u8),
+    /// The user asked to stop the emulator.
+    Quit,
+}
+
+/// Where key presses come from.
+pub trait InputSource {
+    /// Everything that happened since the last poll. Must not block.
+    fn poll(&mut self) -> Vec<KeyEvent>;
+}
+
+/// Events queued up by hand, for tests and hosts that deliver input themselves.
+#[derive(Debug, Default)]
+pub struct ProgrammaticInput {
+    events: VecDeque<KeyEvent>,
+}
+
+impl ProgrammaticInput {
+    pub fn push(&mut self, event: KeyEvent) {
+        self.events.push_back(event);
+    }
+}
+
+impl InputSource for ProgrammaticInput {
+    fn poll(&mut self) -> Vec<KeyEvent> {
+        self.events.drain(..).collect()
+    }
+}
+
+/// Reads the terminal through crossterm. Esc and Ctrl-C quit.
+///
+/// Terminals don't report key releases, so a key counts as held until
+/// the poll after the one that saw it.
+#[cfg(feature = "std")]
+#[derive(Debug, Default)]
+pub struct CrosstermInput {
+    pub keymap: HexKeyMap,
+    held: Vec<u8>,
+}
+
+#[cfg(feature = "std")]
+impl CrosstermInput {
+    /// What a terminal key press means for the emulator: `Quit` for Esc and Ctrl-C, `Pressed`
+    /// for a key in the keymap, and `None` for anything else.
+    pub fn translate(&self, key: &crossterm::event::KeyEvent) -> Option<KeyEvent> {
+        use crossterm::event::{KeyCode, KeyModifiers};
+
+        // In raw mode Ctrl-C arrives as a key press rather than SIGINT.
+        let ctrl_c = key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+        if key.code == KeyCode::Esc || ctrl_c {
+            return Some(KeyEvent::Quit);
+        }
+        self.keymap.0.get(&key.code).map(|mapped_value| KeyEvent::Pressed(*mapped_value as u8))
+    }
+}
+
+#[cfg(feature = "std")]
+impl InputSource for CrosstermInput {
+    fn poll(&mut self) -> Vec<KeyEvent> {
+        use crossterm::event::{self, Event};
+
+        let mut events: Vec<KeyEvent> = self.held.drain(..).map(KeyEvent::Released).collect();
+
+        while let Ok(true) = event::poll(std::time::Duration::ZERO) {
+            let Ok(Event::Key(key)) = event::read() else {
+                continue;
+            };
+            match self.translate(&key) {
+                Some(KeyEvent::Pressed(mapped_value)) => {
+                    events.retain(|event| *event != KeyEvent::Released(mapped_value));
+                    events.push(KeyEvent::Pressed(mapped_value));
+                    self.held.push(mapped_value);
+                },
+                Some(event) => events.push(event),
+                None => {},
+            }
+        }
+        events
+    }
+}
+
+/// Puts the terminal into raw mode on an alternate screen, and puts it back
+/// the way it was when dropped, including on errors and panics.
+#[cfg(feature = "std")]
+pub struct TerminalGuard;
+
+#[cfg(feature = "std")]
+impl TerminalGuard {
+    pub fn new() -> std::io::Result<Self> {
+        use crossterm::{cursor, execute, terminal};
+
+        terminal::enable_raw_mode()?;
+        execute!(std::io::stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(Self)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        use crossterm::{cursor, execute, terminal};
+
+        let _ = execute!(std::io::stdout(), cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Draws the screen to a terminal (stdout by default), from the top-left corner. For sound on
+/// the same terminal, pass a `TerminalBell` as the audio.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct TerminalRenderer<W = std::io::Stdout> {
+    out: W,
+}
+
+#[cfg(feature = "std")]
+impl Default for TerminalRenderer {
+    fn default() -> Self {
+        Self::new(std::io::stdout())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> TerminalRenderer<W> {
+    pub fn new(out: W) -> Self {
+        Self { out }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> Renderer for TerminalRenderer<W> {
+    fn render(&mut self, screen: &Screen) {
+        use crossterm::{cursor, queue};
+
+        // Raw mode doesn't translate `\n`, so return the carriage ourselves.
+        let frame = alloc::format!("{}", screen).replace('\n', "\r\n");
+        let _ = queue!(self.out, cursor::MoveTo(0, 0));
+        let _ = write!(self.out, "{}", frame);
+        let _ = self.out.flush();
+    }
+}
+
+/// Rings the terminal bell (`\x07`, stdout by default) for beeps. The sound for
+/// `Chip8::start_with_keymap` when the terminal is all there is.
+///
+/// A bell is a single chime that can't be held, so it rings once as the sound timer becomes
+/// active and stays quiet until the next beep.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct TerminalBell<W = std::io::Stdout> {
+    out: W,
+}
+
+#[cfg(feature = "std")]
+impl Default for TerminalBell {
+    fn default() -> Self {
+        Self::new(std::io::stdout())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> TerminalBell<W> {
+    pub fn new(out: W) -> Self {
+        Self { out }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> Audio for TerminalBell<W> {
+    fn start_tone(&mut self) {
+        let _ = self.out.write_all(b"\x07");
+        let _ = self.out.flush();
+    }
+    fn stop_tone(&mut self) {}
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn programmatic_input_drains_in_order() {
+        let mut input = ProgrammaticInput::default();
+        input.push(KeyEvent::Pressed(0xA));
+        input.push(KeyEvent::Quit);
+
+        assert_eq!(input.poll(), vec![KeyEvent::Pressed(0xA), KeyEvent::Quit]);
+        assert!(input.poll().is_empty());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn esc_and_ctrl_c_quit() {
+        use crossterm::event::{KeyCode, KeyModifiers};
+        let key = |code, modifiers| crossterm::event::KeyEvent::new(code, modifiers);
+
+        let input = CrosstermInput::default();
+        assert_eq!(input.translate(&key(KeyCode::Esc, KeyModifiers::NONE)), Some(KeyEvent::Quit));
+        assert_eq!(input.translate(&key(KeyCode::Char('c'), KeyModifiers::CONTROL)), Some(KeyEvent::Quit));
+        // A plain `c` is just the key for 0xE.
+        assert_eq!(input.translate(&key(KeyCode::Char('c'), KeyModifiers::NONE)), Some(KeyEvent::Pressed(0xE)));
+        assert_eq!(input.translate(&key(KeyCode::F(5), KeyModifiers::NONE)), None);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn terminal_bell_rings_once_per_beep() -> Result<(), crate::virtual_machine::Chip8Error> {
+        use crate::virtual_machine::Chip8;
+
+        let program = [0x60, 0x05, 0xF0, 0x18, 0x12, 0x04]; // sound timer = 5, then loop
+        let mut chip = Chip8::from_rom_bytes(&program)?;
+        let mut renderer = TerminalRenderer::new(Vec::new());
+        let mut bell = TerminalBell::new(Vec::new());
+        // The beep lasts 5 frames; the rest are silent.
+        for _ in 0..10 {
+            chip.run_frame(&mut renderer, &mut bell)?;
+        }
+        assert_eq!(bell.into_inner(), b"\x07");
+        assert!(!renderer.into_inner().contains(&0x07));
+        Ok(())
+    }
+}
@@ -0,0 +1,165 @@
+//! Running a machine on its own thread while another one renders and feeds it input.
+//!
+//! The machine lives behind one `Mutex`. The CPU thread holds the lock for exactly one frame
+//! at a time and sleeps with it released, so the UI thread gets its turn between frames.
+//! Every `Chip8Handle` method takes the lock only for as long as it needs to copy the screen
+//! or flip a key, and never while holding another lock. The one way to deadlock is calling
+//! back into the handle from inside `Chip8Handle::with_chip`'s closure, so don't.
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex, MutexGuard,
+};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use super::{Bus, Chip8, Chip8Error, Screen, StopReason};
+
+
+/// A `Chip8` shared between a background CPU thread and the caller's thread.
+#[derive(Debug)]
+pub struct Chip8Handle {
+    chip: Arc<Mutex<Chip8>>,
+    stop: Arc<AtomicBool>,
+    cpu: Option<JoinHandle<Result<StopReason, Chip8Error>>>,
+}
+
+impl Chip8Handle {
+    pub fn new(chip: Chip8) -> Self {
+        Self {
+            chip: Arc::new(Mutex::new(chip)),
+            stop: Arc::new(AtomicBool::new(false)),
+            cpu: None,
+        }
+    }
+
+    /// Start running frames at 60 Hz on a background thread, until `stop` or a stop reason.
+    /// Does nothing if it's already running.
+    pub fn run(&mut self) {
+        if self.cpu.is_some() {
+            return;
+        }
+        self.stop.store(false, Ordering::SeqCst);
+        let (chip, stop) = (self.chip.clone(), self.stop.clone());
+        let frame = Duration::from_secs(1) / 60;
+
+        self.cpu = Some(std::thread::spawn(move || {
+            while !stop.load(Ordering::SeqCst) {
+                let started = Instant::now();
+                let reason = lock(&chip).run_frame(&mut (), &mut ())?;
+                if let Some(reason) = reason {
+                    return Ok(reason);
+                }
+                if let Some(remaining) = frame.checked_sub(started.elapsed()) {
+                    std::thread::sleep(remaining);
+                }
+            }
+            Ok(StopReason::UserQuit)
+        }));
+    }
+
+    /// Stop the background thread and wait for it. Returns why it stopped, or `None` if it
+    /// wasn't running. `StopReason::UserQuit` means it was stopped from here, and
+    /// `Chip8Error::CpuThreadPanicked` that it had already died.
+    pub fn stop(&mut self) -> Option<Result<StopReason, Chip8Error>> {
+        let cpu = self.cpu.take()?;
+        self.stop.store(true, Ordering::SeqCst);
+        Some(cpu.join().unwrap_or_else(|panic| {
+            let message = panic.downcast_ref::<&str>().map(|message| message.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            Err(Chip8Error::CpuThreadPanicked(message))
+        }))
+    }
+
+    /// A copy of the screen as of the last complete instruction.
+    pub fn framebuffer(&self) -> Screen {
+        lock(&self.chip).bus().screen().clone()
+    }
+
+    pub fn press_key(&self, key: u8) {
+        lock(&self.chip).bus_mut().keypad_mut().set(key, true);
+    }
+
+    pub fn release_key(&self, key: u8) {
+        lock(&self.chip).bus_mut().keypad_mut().set(key, false);
+    }
+
+    /// Run `f` with the machine locked, e.g. to read registers or load a savestate.
+    pub fn with_chip<T>(&self, f: impl FnOnce(&mut Chip8) -> T) -> T {
+        f(&mut lock(&self.chip))
+    }
+}
+
+impl Drop for Chip8Handle {
+    fn drop(&mut self) {
+        if let Some(cpu) = self.cpu.take() {
+            self.stop.store(true, Ordering::SeqCst);
+            let _ = cpu.join();
+        }
+    }
+}
+
+/// A panic on the CPU thread is reported by `stop`; the machine itself is still usable.
+fn lock(chip: &Mutex<Chip8>) -> MutexGuard<'_, Chip8> {
+    chip.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ui_thread_and_cpu_thread_share_the_machine() -> Result<(), Chip8Error> {
+        let chip = Chip8::from_rom_bytes(&[
+            0xE1, 0xA1, // skip unless key 1 is pressed
+            0x70, 0x01, // V0 += 1
+            0x12, 0x00, // again
+        ])?;
+        let mut handle = Chip8Handle::new(chip);
+        handle.run();
+
+        let ui = std::thread::scope(|scope| {
+            scope.spawn(|| {
+                for frame in 0..200 {
+                    if frame % 2 == 0 {
+                        handle.press_key(0x1);
+                    } else {
+                        handle.release_key(0x1);
+                    }
+                    assert_eq!(handle.framebuffer().len(), 64 * 32);
+                }
+            }).join()
+        });
+        assert!(ui.is_ok());
+
+        // Give the CPU thread a chance to run a frame, however the threads were scheduled.
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while handle.with_chip(|chip| chip.cycles()) == 0 && std::time::Instant::now() < deadline {
+            std::thread::yield_now();
+        }
+        assert!(handle.with_chip(|chip| chip.cycles()) > 0);
+        assert_eq!(handle.stop().unwrap()?, StopReason::UserQuit);
+        Ok(())
+    }
+
+    #[test]
+    fn stop_reports_a_panicked_cpu_thread() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::from_rom_bytes(&[0x03, 0x00, 0x12, 0x00])?; // machine code call
+        chip.set_machine_routine_handler(|_, _| panic!("no RCA 1802 here"));
+        let mut handle = Chip8Handle::new(chip);
+        handle.run();
+
+        let stopped = loop {
+            if handle.cpu.as_ref().is_some_and(|cpu| cpu.is_finished()) {
+                break handle.stop();
+            }
+            std::thread::yield_now();
+        };
+        assert!(matches!(stopped, Some(Err(Chip8Error::CpuThreadPanicked(message))) if message == "no RCA 1802 here"));
+        // The machine is still there to look at.
+        assert_eq!(handle.with_chip(|chip| chip.cycles()), 0);
+        Ok(())
+    }
+}
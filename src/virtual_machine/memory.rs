@@ -1,24 +1,35 @@
-use std::ops::{Index, DerefMut, Deref, IndexMut};
+use core::ops::{Index, DerefMut, Deref, IndexMut};
+
+#[cfg(feature = "std")]
+use thiserror::Error;
 
 use super::FontSet;
 
 
+pub const MEMORY_SIZE: usize = 4096;
+
+#[derive(Debug)]
+#[cfg_attr(feature = "std", derive(Error))]
+pub enum MemoryError {
+    #[cfg_attr(feature = "std", error("Address `{0:#05x}` is outside of the 4K of memory."))]
+    OutOfBounds(usize),
+}
 
 #[derive(Debug)]
 pub struct Memory {
-    pub(crate) _inner: [u8; 4096]
+    pub(crate) _inner: [u8; MEMORY_SIZE]
 }
 
 impl Default for Memory {
     fn default() -> Self {
         Self {
-            _inner: [0; 4096]
+            _inner: [0; MEMORY_SIZE]
         }
     }
 }
 
 impl Deref for Memory {
-    type Target = [u8; 4096];
+    type Target = [u8; MEMORY_SIZE];
     fn deref(&self) -> &Self::Target {
         &self._inner
     }
@@ -0,0 +1,104 @@
+use super::{Bus, Chip8, Chip8Error, OpCode};
+
+
+/// One part of running an instruction, as reported by `Chip8::micro_step`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// The two bytes at the program counter were read.
+    Fetch { pc: u16, word: u16 },
+    /// The word was identified as an instruction.
+    Decode { pc: u16, opcode: OpCode },
+    /// The instruction changed registers, memory, the screen or the timers. The program counter
+    /// still points at it.
+    Execute { pc: u16, opcode: OpCode },
+    /// The program counter moved on to the next instruction.
+    PcUpdate { pc: u16, next_pc: u16 },
+}
+
+/// How far `micro_step` got through the current instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MicroState {
+    Fetched { pc: u16, word: u16 },
+    Decoded { pc: u16, opcode: OpCode },
+    Executed { pc: u16, next_pc: u16 },
+}
+
+impl<B: Bus> Chip8<B> {
+    /// Advance the current instruction by one phase: fetch, decode, execute, then move the
+    /// program counter. The four phases together do exactly what one `step` does, so a `step`
+    /// in the middle simply finishes the instruction.
+    pub fn micro_step(&mut self) -> Result<Phase, Chip8Error> {
+        match self.micro.take() {
+            None => {
+                let (pc, word) = (self.program_counter.read(), self.fetch_opcode());
+                self.micro = Some(MicroState::Fetched { pc, word });
+                Ok(Phase::Fetch { pc, word })
+            },
+            Some(MicroState::Fetched { pc, word }) => {
+                let opcode = OpCode::try_from(word)?;
+                self.micro = Some(MicroState::Decoded { pc, opcode });
+                Ok(Phase::Decode { pc, opcode })
+            },
+            Some(MicroState::Decoded { pc, opcode }) => {
+                // The whole instruction runs here so hooks, tracing and rewind see a normal step;
+                // only the program counter is held back until the next phase.
+                self.step()?;
+                let next_pc = self.program_counter.read();
+                self.program_counter.write(pc)?;
+                self.micro = Some(MicroState::Executed { pc, next_pc });
+                Ok(Phase::Execute { pc, opcode })
+            },
+            Some(MicroState::Executed { pc, next_pc }) => {
+                self.program_counter.write(next_pc)?;
+                Ok(Phase::PcUpdate { pc, next_pc })
+            },
+        }
+    }
+
+    /// Finish an instruction `micro_step` started. Returns whether it had already executed,
+    /// in which case only its program counter update was left to do.
+    pub(crate) fn finish_micro_step(&mut self) -> Result<bool, Chip8Error> {
+        match self.micro.take() {
+            Some(MicroState::Executed { next_pc, .. }) => {
+                self.program_counter.write(next_pc)?;
+                Ok(true)
+            },
+            _ => Ok(false),
+        }
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn one_instruction_in_four_phases() -> Result<(), Chip8Error> {
+        let rom = [0x60, 0x2A, 0x61, 0x07];
+        let mut stepped = Chip8::from_rom_bytes(&rom)?;
+        let mut chip = Chip8::from_rom_bytes(&rom)?;
+
+        assert_eq!(chip.micro_step()?, Phase::Fetch { pc: 0x200, word: 0x602A });
+        let opcode = OpCode::try_from(0x602A)?;
+        assert_eq!(chip.micro_step()?, Phase::Decode { pc: 0x200, opcode });
+        assert_eq!(chip.data_registers.read('0')?, 0);
+
+        assert_eq!(chip.micro_step()?, Phase::Execute { pc: 0x200, opcode });
+        assert_eq!(chip.data_registers.read('0')?, 0x2A);
+        assert_eq!(chip.program_counter.read(), 0x200);
+
+        assert_eq!(chip.micro_step()?, Phase::PcUpdate { pc: 0x200, next_pc: 0x202 });
+        stepped.step()?;
+        assert_eq!(chip.save_state_bytes(), stepped.save_state_bytes());
+        assert_eq!(chip.cycles(), stepped.cycles());
+
+        // A step halfway through finishes the instruction instead of starting the next one.
+        chip.micro_step()?;
+        chip.micro_step()?;
+        chip.step()?;
+        stepped.step()?;
+        assert_eq!(chip.save_state_bytes(), stepped.save_state_bytes());
+        Ok(())
+    }
+}
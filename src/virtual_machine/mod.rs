@@ -3,9 +3,49 @@ mod opcode;
 mod register;
 mod chip8;
 mod fonts;
+mod bus;
+mod quirks;
+mod trace;
+mod frontend;
+mod roms;
+mod clock;
+mod savestate;
+mod validate;
+mod debug_view;
+mod diagnose;
+mod bmp;
+mod rewind;
+mod micro;
+mod alu;
+mod tripwire;
+mod random_log;
+mod coverage;
+mod disassemble;
+mod profile;
+mod tune;
+#[cfg(feature = "std")]
+mod handle;
 
 pub use memory::*;
 pub use opcode::*;
 pub use register::*;
 pub use chip8::*;
-pub use fonts::*;
\ No newline at end of file
+pub use fonts::*;
+pub use bus::*;
+pub use quirks::*;
+pub use trace::*;
+pub use frontend::*;
+pub use roms::*;
+pub use clock::*;
+pub use savestate::*;
+pub use validate::*;
+pub use debug_view::*;
+pub use diagnose::*;
+pub use bmp::*;
+pub use coverage::*;
+pub use micro::*;
+pub use disassemble::*;
+pub use profile::*;
+pub use tune::*;
+#[cfg(feature = "std")]
+pub use handle::*;
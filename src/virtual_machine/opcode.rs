@@ -1,8 +1,9 @@
 #[deny(clippy::incorrect_ident_case)]
 
+#[cfg(feature = "std")]
 use thiserror::Error;
 
-use crate::data_structures::{Nibble, NibblePair};
+use crate::data_structures::NibbleQuad;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum OpKind {
@@ -22,6 +23,30 @@ pub enum OpKind {
     Timer
 }
 
+impl OpKind {
+    pub fn all() -> &'static [OpKind] {
+        &[
+            Self::Assig, Self::BCD, Self::BitOp, Self::Call, Self::Cond, Self::Const, Self::Display,
+            Self::Flow, Self::KeyOp, Self::Math, Self::MEM, Self::Rand, Self::Sound, Self::Timer,
+        ]
+    }
+}
+
+/// Which of an instruction's fields mean something, e.g. to color each operand on its own.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct OperandSet {
+    /// The X register, the second nibble.
+    pub x: bool,
+    /// The Y register, the third nibble.
+    pub y: bool,
+    /// A 4-bit constant, the last nibble.
+    pub n: bool,
+    /// An 8-bit constant, the low byte.
+    pub nn: bool,
+    /// A 12-bit address, everything but the first nibble.
+    pub nnn: bool,
+}
+
 /// #### The symbols:
 /// - NNN: Address
 /// - NN: 8-bit constant
@@ -35,6 +60,11 @@ pub enum OpLiteral {
     _0NNN,
     _00E0,
     _00EE,
+    _00CN,
+    _00DN,
+    _00FD,
+    _00FE,
+    _00FF,
     _1NNN,
     _2NNN,
     _3XNN,
@@ -66,11 +96,103 @@ pub enum OpLiteral {
     _FX29,
     _FX33,
     _FX55,
-    _FX65
+    _FX65,
+    _FX75,
+    _FX85
+}
+
+impl OpLiteral {
+    /// The original CHIP-8 instruction set, without the SUPER-CHIP additions.
+    pub const BASE: [OpLiteral; 35] = [
+        Self::_0NNN, Self::_00E0, Self::_00EE, Self::_1NNN, Self::_2NNN, Self::_3XNN, Self::_4XNN,
+        Self::_5XY0, Self::_6XNN, Self::_7XNN, Self::_8XY0, Self::_8XY1, Self::_8XY2, Self::_8XY3,
+        Self::_8XY4, Self::_8XY5, Self::_8XY6, Self::_8XY7, Self::_8XYE, Self::_9XY0, Self::_ANNN,
+        Self::_BNNN, Self::_CXNN, Self::_DXYN, Self::_EX9E, Self::_EXA1, Self::_FX07, Self::_FX0A,
+        Self::_FX15, Self::_FX18, Self::_FX1E, Self::_FX29, Self::_FX33, Self::_FX55, Self::_FX65,
+    ];
+
+    /// How many literals there are, SUPER-CHIP ones included.
+    pub const COUNT: usize = Self::_FX85 as usize + 1;
+
+    /// Every literal, SUPER-CHIP ones included, in declaration order.
+    pub fn all() -> &'static [OpLiteral] {
+        &[
+            Self::_0NNN, Self::_00E0, Self::_00EE, Self::_00CN, Self::_00DN, Self::_00FD, Self::_00FE,
+            Self::_00FF, Self::_1NNN, Self::_2NNN, Self::_3XNN, Self::_4XNN, Self::_5XY0, Self::_6XNN,
+            Self::_7XNN, Self::_8XY0, Self::_8XY1, Self::_8XY2, Self::_8XY3, Self::_8XY4, Self::_8XY5,
+            Self::_8XY6, Self::_8XY7, Self::_8XYE, Self::_9XY0, Self::_ANNN, Self::_BNNN, Self::_CXNN,
+            Self::_DXYN, Self::_EX9E, Self::_EXA1, Self::_FX07, Self::_FX0A, Self::_FX15, Self::_FX18,
+            Self::_FX1E, Self::_FX29, Self::_FX33, Self::_FX55, Self::_FX65, Self::_FX75, Self::_FX85,
+        ]
+    }
+
+    /// Which fields the instruction uses, going by the letters in its name.
+    pub fn operands(&self) -> OperandSet {
+        let none = OperandSet::default();
+        match self {
+            Self::_00E0 | Self::_00EE | Self::_00FD | Self::_00FE | Self::_00FF => none,
+            Self::_0NNN | Self::_1NNN | Self::_2NNN | Self::_ANNN | Self::_BNNN => OperandSet { nnn: true, ..none },
+            Self::_00CN | Self::_00DN => OperandSet { n: true, ..none },
+            Self::_3XNN | Self::_4XNN | Self::_6XNN | Self::_7XNN | Self::_CXNN => OperandSet { x: true, nn: true, ..none },
+            Self::_5XY0 | Self::_8XY0 | Self::_8XY1 | Self::_8XY2 | Self::_8XY3 | Self::_8XY4 | Self::_8XY5
+            | Self::_8XY6 | Self::_8XY7 | Self::_8XYE | Self::_9XY0 => OperandSet { x: true, y: true, ..none },
+            Self::_DXYN => OperandSet { x: true, y: true, n: true, ..none },
+            Self::_EX9E | Self::_EXA1 | Self::_FX07 | Self::_FX0A | Self::_FX15 | Self::_FX18 | Self::_FX1E
+            | Self::_FX29 | Self::_FX33 | Self::_FX55 | Self::_FX65 | Self::_FX75 | Self::_FX85 => OperandSet { x: true, ..none },
+        }
+    }
+
+    /// What the instruction does, in a sentence or two.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::_0NNN => "Calls the machine code routine at NNN. Skipped unless a handler is installed.",
+            Self::_00E0 => "Clears the screen.",
+            Self::_00EE => "Returns from a subroutine.",
+            Self::_00CN => "Scrolls the screen down by N rows (SUPER-CHIP).",
+            Self::_00DN => "Scrolls the screen up by N rows (XO-CHIP).",
+            Self::_00FD => "Exits the interpreter (SUPER-CHIP).",
+            Self::_00FE => "Switches to the low resolution display and clears it (SUPER-CHIP).",
+            Self::_00FF => "Switches to the 128x64 display and clears it (SUPER-CHIP).",
+            Self::_1NNN => "Jumps to NNN.",
+            Self::_2NNN => "Calls the subroutine at NNN.",
+            Self::_3XNN => "Skips the next instruction if VX equals NN.",
+            Self::_4XNN => "Skips the next instruction if VX does not equal NN.",
+            Self::_5XY0 => "Skips the next instruction if VX equals VY.",
+            Self::_6XNN => "Sets VX to NN.",
+            Self::_7XNN => "Adds NN to VX, wrapping. VF is not changed.",
+            Self::_8XY0 => "Sets VX to VY.",
+            Self::_8XY1 => "Sets VX to VX | VY.",
+            Self::_8XY2 => "Sets VX to VX & VY.",
+            Self::_8XY3 => "Sets VX to VX ^ VY.",
+            Self::_8XY4 => "Adds VY to VX. VF is 1 if it carried and 0 if not.",
+            Self::_8XY5 => "Subtracts VY from VX. VF is 0 if it borrowed and 1 if not.",
+            Self::_8XY6 => "Shifts VX right by one. VF is the bit shifted out.",
+            Self::_8XY7 => "Sets VX to VY minus VX. VF is 0 if it borrowed and 1 if not.",
+            Self::_8XYE => "Shifts VX left by one. VF is the bit shifted out.",
+            Self::_9XY0 => "Skips the next instruction if VX does not equal VY.",
+            Self::_ANNN => "Sets I to NNN.",
+            Self::_BNNN => "Jumps to NNN plus V0.",
+            Self::_CXNN => "Sets VX to a random byte ANDed with NN.",
+            Self::_DXYN => "Draws the N byte sprite at I at (VX, VY). VF is 1 if any lit pixel was turned off.",
+            Self::_EX9E => "Skips the next instruction if the key in VX is pressed.",
+            Self::_EXA1 => "Skips the next instruction if the key in VX is not pressed.",
+            Self::_FX07 => "Sets VX to the delay timer.",
+            Self::_FX0A => "Waits for a key press and stores the key in VX.",
+            Self::_FX15 => "Sets the delay timer to VX.",
+            Self::_FX18 => "Sets the sound timer to VX.",
+            Self::_FX1E => "Adds VX to I. VF is not changed.",
+            Self::_FX29 => "Sets I to the font sprite for the hex digit in VX.",
+            Self::_FX33 => "Stores the decimal digits of VX at I, I + 1 and I + 2.",
+            Self::_FX55 => "Stores V0 to VX in memory starting at I.",
+            Self::_FX65 => "Loads V0 to VX from memory starting at I.",
+            Self::_FX75 => "Stores V0 to VX in the RPL user flags, X at most 7 (SUPER-CHIP).",
+            Self::_FX85 => "Loads V0 to VX from the RPL user flags, X at most 7 (SUPER-CHIP).",
+        }
+    }
 }
 
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct OpCode {
     pub(crate) value: u16,
     pub(crate) literal: OpLiteral,
@@ -78,9 +200,10 @@ pub struct OpCode {
 }
 
 
-#[derive(Error, Debug)]
+#[derive(Debug)]
+#[cfg_attr(feature = "std", derive(Error))]
 pub enum OpCodeError {
-    #[error("Failed to convert `{0}` to a known opcode. ")]
+    #[cfg_attr(feature = "std", error("Failed to convert `{0}` to a known opcode. "))]
     Unknown(u16)
 }
 
@@ -88,20 +211,17 @@ pub enum OpCodeError {
 impl TryFrom<u16> for OpCode {
     type Error = OpCodeError;
     fn try_from(value: u16) -> Result<Self, Self::Error> {
-        let high_byte = (value >> 8) as u8;
-        let low_byte = (value & 0b1111_1111u16) as u8;
-
-        let high_pair: NibblePair = high_byte.into();
-        let low_pair: NibblePair = low_byte.into();
-
-        let first = high_pair.high.to_hex_char();
-        let second = high_pair.low.to_hex_char();
-        let third = low_pair.high.to_hex_char();
-        let fourth = low_pair.low.to_hex_char();
+        let [first, second, third, fourth] = NibbleQuad::from(value).to_hex_chars();
 
         match (first, second, third, fourth) {
             ('0', '0', 'E', '0')  => Ok(OpCode { value, literal: OpLiteral::_00E0, kind: OpKind::Display }),
             ('0', '0', 'E', 'E')  => Ok(OpCode { value, literal: OpLiteral::_00EE, kind: OpKind::Flow }),
+            // Scrolls, which would otherwise be taken for machine code calls below.
+            ('0', '0', 'C', _)  => Ok(OpCode { value, literal: OpLiteral::_00CN, kind: OpKind::Display }),
+            ('0', '0', 'D', _)  => Ok(OpCode { value, literal: OpLiteral::_00DN, kind: OpKind::Display }),
+            ('0', '0', 'F', 'D')  => Ok(OpCode { value, literal: OpLiteral::_00FD, kind: OpKind::Flow }),
+            ('0', '0', 'F', 'E')  => Ok(OpCode { value, literal: OpLiteral::_00FE, kind: OpKind::Display }),
+            ('0', '0', 'F', 'F')  => Ok(OpCode { value, literal: OpLiteral::_00FF, kind: OpKind::Display }),
             ('0', _, _, _) => Ok(OpCode { value, literal: OpLiteral::_0NNN, kind: OpKind::Call }),
             ('1', _, _, _) => Ok(OpCode { value, literal: OpLiteral::_1NNN, kind: OpKind::Flow }),
             ('2', _, _, _) => Ok(OpCode { value, literal: OpLiteral::_2NNN, kind: OpKind::Flow }),
@@ -135,15 +255,113 @@ impl TryFrom<u16> for OpCode {
             ('F', _, '3', '3') => Ok(OpCode { value, literal: OpLiteral::_FX33, kind: OpKind::MEM }),
             ('F', _, '5', '5') => Ok(OpCode { value, literal: OpLiteral::_FX55, kind: OpKind::MEM }),
             ('F', _, '6', '5') => Ok(OpCode { value, literal: OpLiteral::_FX65, kind: OpKind::MEM }),
+            ('F', _, '7', '5') => Ok(OpCode { value, literal: OpLiteral::_FX75, kind: OpKind::MEM }),
+            ('F', _, '8', '5') => Ok(OpCode { value, literal: OpLiteral::_FX85, kind: OpKind::MEM }),
             _ => Err(OpCodeError::Unknown(value))
         }
     }
 }
 
 
+impl OpCode {
+    /// What sort of instruction this is, e.g. for grouping in traces and profiles.
+    pub fn kind(&self) -> OpKind {
+        self.kind
+    }
+
+    /// Which fields this instruction uses, see `OpLiteral::operands`.
+    pub fn operands(&self) -> OperandSet {
+        self.literal.operands()
+    }
+
+    pub fn nibbles(&self) -> NibbleQuad {
+        NibbleQuad::from(self.value)
+    }
+
+    /// The X register operand, i.e. the second nibble.
+    pub fn x(&self) -> u8 {
+        self.nibbles().0[1].to_u8()
+    }
+
+    /// The Y register operand, i.e. the third nibble.
+    pub fn y(&self) -> u8 {
+        self.nibbles().0[2].to_u8()
+    }
+
+    /// The 4-bit constant, i.e. the last nibble.
+    pub fn n(&self) -> u8 {
+        self.nibbles().0[3].to_u8()
+    }
+
+    /// The 8-bit constant, i.e. the low byte.
+    pub fn nn(&self) -> u8 {
+        (self.value & 0x00FF) as u8
+    }
+
+    /// The 12-bit address.
+    pub fn nnn(&self) -> u16 {
+        self.value & 0x0FFF
+    }
+}
+
+
+/// Renders the conventional assembly mnemonic, e.g. `LD V1, 0x2a` or `DRW V0, V1, 5`.
+impl core::fmt::Display for OpCode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let (x, y, n) = (self.x(), self.y(), self.n());
+        let (nn, nnn) = (self.nn(), self.nnn());
+
+        match self.literal {
+            OpLiteral::_0NNN => write!(f, "SYS {:#05x}", nnn),
+            OpLiteral::_00E0 => write!(f, "CLS"),
+            OpLiteral::_00EE => write!(f, "RET"),
+            OpLiteral::_00CN => write!(f, "SCD {}", n),
+            OpLiteral::_00DN => write!(f, "SCU {}", n),
+            OpLiteral::_00FD => write!(f, "EXIT"),
+            OpLiteral::_00FE => write!(f, "LOW"),
+            OpLiteral::_00FF => write!(f, "HIGH"),
+            OpLiteral::_1NNN => write!(f, "JP {:#05x}", nnn),
+            OpLiteral::_2NNN => write!(f, "CALL {:#05x}", nnn),
+            OpLiteral::_3XNN => write!(f, "SE V{:X}, {:#04x}", x, nn),
+            OpLiteral::_4XNN => write!(f, "SNE V{:X}, {:#04x}", x, nn),
+            OpLiteral::_5XY0 => write!(f, "SE V{:X}, V{:X}", x, y),
+            OpLiteral::_6XNN => write!(f, "LD V{:X}, {:#04x}", x, nn),
+            OpLiteral::_7XNN => write!(f, "ADD V{:X}, {:#04x}", x, nn),
+            OpLiteral::_8XY0 => write!(f, "LD V{:X}, V{:X}", x, y),
+            OpLiteral::_8XY1 => write!(f, "OR V{:X}, V{:X}", x, y),
+            OpLiteral::_8XY2 => write!(f, "AND V{:X}, V{:X}", x, y),
+            OpLiteral::_8XY3 => write!(f, "XOR V{:X}, V{:X}", x, y),
+            OpLiteral::_8XY4 => write!(f, "ADD V{:X}, V{:X}", x, y),
+            OpLiteral::_8XY5 => write!(f, "SUB V{:X}, V{:X}", x, y),
+            OpLiteral::_8XY6 => write!(f, "SHR V{:X}, V{:X}", x, y),
+            OpLiteral::_8XY7 => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            OpLiteral::_8XYE => write!(f, "SHL V{:X}, V{:X}", x, y),
+            OpLiteral::_9XY0 => write!(f, "SNE V{:X}, V{:X}", x, y),
+            OpLiteral::_ANNN => write!(f, "LD I, {:#05x}", nnn),
+            OpLiteral::_BNNN => write!(f, "JP V0, {:#05x}", nnn),
+            OpLiteral::_CXNN => write!(f, "RND V{:X}, {:#04x}", x, nn),
+            OpLiteral::_DXYN => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            OpLiteral::_EX9E => write!(f, "SKP V{:X}", x),
+            OpLiteral::_EXA1 => write!(f, "SKNP V{:X}", x),
+            OpLiteral::_FX07 => write!(f, "LD V{:X}, DT", x),
+            OpLiteral::_FX0A => write!(f, "LD V{:X}, K", x),
+            OpLiteral::_FX15 => write!(f, "LD DT, V{:X}", x),
+            OpLiteral::_FX18 => write!(f, "LD ST, V{:X}", x),
+            OpLiteral::_FX1E => write!(f, "ADD I, V{:X}", x),
+            OpLiteral::_FX29 => write!(f, "LD F, V{:X}", x),
+            OpLiteral::_FX33 => write!(f, "LD B, V{:X}", x),
+            OpLiteral::_FX55 => write!(f, "LD [I], V{:X}", x),
+            OpLiteral::_FX65 => write!(f, "LD V{:X}, [I]", x),
+            OpLiteral::_FX75 => write!(f, "LD R, V{:X}", x),
+            OpLiteral::_FX85 => write!(f, "LD V{:X}, R", x),
+        }
+    }
+}
+
+
 #[cfg(test)]
 pub mod tests {
-    use super::{OpCode, OpCodeError};
+    use super::{OpCode, OpCodeError, OpKind, OpLiteral, OperandSet};
     use std::result::Result;
     use std::error::Error;
 
@@ -157,7 +375,55 @@ pub mod tests {
             }
         }
 
-        assert_eq!(counter, 13647);
+        assert_eq!(counter, 13615);
+        Ok(())
+    }
+
+    #[test]
+    fn opcode_mnemonic() -> Result<(), Box<dyn Error>> {
+        assert_eq!(OpCode::try_from(0x00E0)?.to_string(), "CLS");
+        assert_eq!(OpCode::try_from(0x612A)?.to_string(), "LD V1, 0x2a");
+        assert_eq!(OpCode::try_from(0xD015)?.to_string(), "DRW V0, V1, 5");
+        assert_eq!(OpCode::try_from(0x2345)?.to_string(), "CALL 0x345");
         Ok(())
     }
+
+    #[test]
+    fn opcode_kind() -> Result<(), Box<dyn Error>> {
+        assert_eq!(OpCode::try_from(0xD123)?.kind(), OpKind::Display);
+        assert_eq!(OpCode::try_from(0x8124)?.kind(), OpKind::Math);
+        assert!(OpKind::all().contains(&OpKind::Timer));
+        Ok(())
+    }
+
+    #[test]
+    fn scrolls_are_not_machine_code_calls() -> Result<(), Box<dyn Error>> {
+        let scroll_down = OpCode::try_from(0x00C4)?;
+        assert_eq!(scroll_down.literal, OpLiteral::_00CN);
+        assert_eq!(scroll_down.n(), 4);
+        assert_eq!(scroll_down.to_string(), "SCD 4");
+        assert_eq!(OpCode::try_from(0x00D2)?.literal, OpLiteral::_00DN);
+        assert_eq!(OpCode::try_from(0x0ABC)?.literal, OpLiteral::_0NNN);
+        assert_eq!(OpCode::try_from(0x01C4)?.literal, OpLiteral::_0NNN);
+        Ok(())
+    }
+
+    #[test]
+    fn draw_has_x_y_and_n_operands() -> Result<(), Box<dyn Error>> {
+        let operands = OpCode::try_from(0xD015)?.operands();
+        assert!(operands.x && operands.y && operands.n);
+        assert!(!operands.nn && !operands.nnn);
+        assert_eq!(OpLiteral::_00E0.operands(), OperandSet::default());
+        Ok(())
+    }
+
+    #[test]
+    fn every_literal_is_listed_and_described() {
+        assert_eq!(OpLiteral::all().len(), 42);
+        assert_eq!(OpLiteral::all().len(), OpLiteral::COUNT);
+        for (idx, literal) in OpLiteral::all().iter().enumerate() {
+            assert_eq!(*literal as usize, idx);
+            assert!(!literal.description().is_empty(), "{:?}", literal);
+        }
+    }
 }
\ No newline at end of file
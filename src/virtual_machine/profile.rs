@@ -0,0 +1,174 @@
+use alloc::{format, string::String, vec::Vec};
+
+use super::{Bus, Chip8, Quirks, MEMORY_SIZE};
+
+
+/// The settings a particular ROM is known to need.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompatProfile {
+    pub quirks: Quirks,
+    pub clock_hz: u32,
+}
+
+impl Default for CompatProfile {
+    fn default() -> Self {
+        Self { quirks: Quirks::default(), clock_hz: 600 }
+    }
+}
+
+/// Known ROMs by the SHA-1 of their bytes, the key community compatibility databases use.
+fn known_roms() -> [([u8; 20], CompatProfile); 3] {
+    [
+        // pong2
+        (hex_digest("1830eb401ba8789a477dfcf294873a5479ebcfe8"), CompatProfile::default()),
+        // tetris
+        (hex_digest("5f518084744bf3cb8733f6e5454dfd1634320563"), CompatProfile::default()),
+        // invaders, written for CHIP-48, so it shifts VX in place and leaves I alone after FX55/FX65.
+        (hex_digest("5c28a5f85289c9d859f95fd5eadbdcb1c30bb08b"), CompatProfile { quirks: Quirks::schip(), clock_hz: 600 }),
+    ]
+}
+
+impl CompatProfile {
+    /// The profile for a known ROM, or `None` if its hash isn't in the built-in table.
+    pub fn for_rom(bytes: &[u8]) -> Option<Self> {
+        let digest = sha1(bytes);
+        known_roms()
+            .into_iter()
+            .find(|(known, _)| *known == digest)
+            .map(|(_, profile)| profile)
+    }
+}
+
+impl<B: Bus> Chip8<B> {
+    /// Switch to the quirks and clock speed in `profile`.
+    pub fn apply_profile(&mut self, profile: CompatProfile) {
+        self.quirks = profile.quirks;
+        self.clock_hz = profile.clock_hz;
+    }
+
+    /// The SHA-1 of the loaded program as 40 lowercase hex digits, the same hash
+    /// `CompatProfile::for_rom` looks ROMs up by. Covers exactly the bytes `load_program_bytes`
+    /// loaded; for a program that got into memory some other way, like a savestate, everything
+    /// from the entry point up to the last non-zero byte.
+    pub fn rom_sha1(&self) -> String {
+        let start = self.entry_point as usize;
+        let end = if self.program_len > 0 {
+            start + self.program_len
+        } else {
+            (start..MEMORY_SIZE).rev().find(|address| self.bus.read(*address) != 0).map_or(start, |last| last + 1)
+        };
+        let program: Vec<u8> = (start..end).map(|address| self.bus.read(address)).collect();
+        sha1(&program).iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+}
+
+/// Parses a 40 character hex digest.
+const fn hex_digest(hex: &str) -> [u8; 20] {
+    const fn nibble(c: u8) -> u8 {
+        match c {
+            b'0'..=b'9' => c - b'0',
+            b'a'..=b'f' => c - b'a' + 10,
+            _ => panic!("not a lowercase hex digit"),
+        }
+    }
+    let hex = hex.as_bytes();
+    assert!(hex.len() == 40);
+    let mut digest = [0u8; 20];
+    let mut idx = 0;
+    while idx < 20 {
+        digest[idx] = nibble(hex[2 * idx]) << 4 | nibble(hex[2 * idx + 1]);
+        idx += 1;
+    }
+    digest
+}
+
+/// SHA-1, as specified in RFC 3174. Only used to look ROMs up, not for anything security related.
+pub(crate) fn sha1(bytes: &[u8]) -> [u8; 20] {
+    let mut state: [u32; 5] = [0x6745_2301, 0xEFCD_AB89, 0x98BA_DCFE, 0x1032_5476, 0xC3D2_E1F0];
+
+    // The message, a 1 bit, zeros up to 8 bytes short of a block, then the length in bits.
+    let padded_len = (bytes.len() + 9).div_ceil(64) * 64;
+    let bit_len = (bytes.len() as u64).wrapping_mul(8).to_be_bytes();
+    let byte_at = |idx: usize| match idx {
+        idx if idx < bytes.len() => bytes[idx],
+        idx if idx == bytes.len() => 0x80,
+        idx if idx >= padded_len - 8 => bit_len[idx - (padded_len - 8)],
+        _ => 0,
+    };
+
+    for block in (0..padded_len).step_by(64) {
+        let mut words = [0u32; 80];
+        for (idx, word) in words.iter_mut().take(16).enumerate() {
+            let offset = block + idx * 4;
+            *word = u32::from_be_bytes([byte_at(offset), byte_at(offset + 1), byte_at(offset + 2), byte_at(offset + 3)]);
+        }
+        for idx in 16..80 {
+            words[idx] = (words[idx - 3] ^ words[idx - 8] ^ words[idx - 14] ^ words[idx - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+        for (idx, word) in words.iter().enumerate() {
+            let (f, k) = match idx {
+                0..=19 => ((b & c) | (!b & d), 0x5A82_7999),
+                20..=39 => (b ^ c ^ d, 0x6ED9_EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1B_BCDC),
+                _ => (b ^ c ^ d, 0xCA62_C1D6),
+            };
+            let temp = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (value, add) in state.iter_mut().zip([a, b, c, d, e]) {
+            *value = value.wrapping_add(add);
+        }
+    }
+
+    let mut digest = [0u8; 20];
+    for (chunk, value) in digest.chunks_mut(4).zip(state) {
+        chunk.copy_from_slice(&value.to_be_bytes());
+    }
+    digest
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use super::super::Chip8Error;
+
+    #[test]
+    fn sha1_matches_the_rfc_examples() {
+        assert_eq!(sha1(b"abc"), hex_digest("a9993e364706816aba3e25717850c26c9cd0d89d"));
+        assert_eq!(
+            sha1(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            hex_digest("84983e441c3bd26ebaae4aa1f95129e5e54670f1"),
+        );
+    }
+
+    #[test]
+    fn rom_sha1_covers_the_loaded_program() -> Result<(), Chip8Error> {
+        // The trailing zero is part of the ROM, so it counts.
+        let chip = Chip8::from_rom_bytes(&[0x60, 0x05, 0x12, 0x02, 0x00])?;
+        assert_eq!(chip.rom_sha1(), "f4e0e91a9dda9bc847f855b872fd854cc6c39531");
+
+        // A savestate doesn't say how long the ROM was.
+        let mut restored = Chip8::new();
+        restored.load_state_bytes(&chip.save_state_bytes())?;
+        assert_eq!(restored.rom_sha1(), "86a8e7581b9232ae5b0ef9218300bd4f7811bbe5");
+        Ok(())
+    }
+
+    #[test]
+    fn known_rom_gets_its_profile() -> Result<(), Chip8Error> {
+        let chip = Chip8::from_rom_bytes(include_bytes!("../../programs/invaders.c8"))?;
+        assert_eq!(chip.quirks(), Quirks::schip());
+
+        let chip = Chip8::from_rom_bytes(&[0x12, 0x00])?;
+        assert_eq!(chip.quirks(), Quirks::default());
+        assert_eq!(CompatProfile::for_rom(&[0x12, 0x00]), None);
+        Ok(())
+    }
+}
@@ -0,0 +1,71 @@
+/// Behaviours that differ between CHIP-8 interpreters.
+///
+/// `Quirks::default()` keeps this interpreter's historical behaviour; use one
+/// of the presets to emulate a specific platform.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Quirks {
+    /// `8XY1`/`8XY2`/`8XY3` reset VF to 0 after the logic operation.
+    pub logic_vf_reset: bool,
+    /// `BNNN` is read as `BXNN` and jumps to `XNN` plus VX instead of `NNN` plus V0.
+    pub jump_vx: bool,
+    /// `FX55`/`FX65` leave I pointing just past the last register stored or loaded.
+    pub index_increment: bool,
+    /// `8XY6`/`8XYE` shift VY and store the result in VX instead of shifting VX in place.
+    pub shift_vy: bool,
+    /// `DXYN` pixels that run off an edge of the display wrap around to the opposite edge
+    /// instead of being clipped.
+    pub sprite_wrap: bool,
+    /// `FX0A` waits for the pressed key to be released before storing it and moving on.
+    pub fx0a_on_release: bool,
+}
+
+impl Quirks {
+    /// The original COSMAC VIP interpreter.
+    pub fn cosmac() -> Self {
+        Self {
+            logic_vf_reset: true,
+            jump_vx: false,
+            index_increment: true,
+            shift_vy: true,
+            sprite_wrap: false,
+            fx0a_on_release: true,
+        }
+    }
+
+    /// SUPER-CHIP 1.1 on the HP48.
+    pub fn schip() -> Self {
+        Self {
+            logic_vf_reset: false,
+            jump_vx: true,
+            index_increment: false,
+            shift_vy: false,
+            sprite_wrap: false,
+            fx0a_on_release: false,
+        }
+    }
+
+    /// Every quirk turned on by itself, along with its field name.
+    pub fn each() -> [(&'static str, Quirks); 6] {
+        let off = Self::default();
+        [
+            ("logic_vf_reset", Self { logic_vf_reset: true, ..off }),
+            ("jump_vx", Self { jump_vx: true, ..off }),
+            ("index_increment", Self { index_increment: true, ..off }),
+            ("shift_vy", Self { shift_vy: true, ..off }),
+            ("sprite_wrap", Self { sprite_wrap: true, ..off }),
+            ("fx0a_on_release", Self { fx0a_on_release: true, ..off }),
+        ]
+    }
+
+    /// The quirks that are on in either `self` or `other`.
+    pub fn union(self, other: Quirks) -> Self {
+        Self {
+            logic_vf_reset: self.logic_vf_reset || other.logic_vf_reset,
+            jump_vx: self.jump_vx || other.jump_vx,
+            index_increment: self.index_increment || other.index_increment,
+            shift_vy: self.shift_vy || other.shift_vy,
+            sprite_wrap: self.sprite_wrap || other.sprite_wrap,
+            fx0a_on_release: self.fx0a_on_release || other.fx0a_on_release,
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use alloc::vec::Vec;
+
+use super::{Bus, Chip8};
+
+
+/// Where `CXNN` gets its random bytes besides the bus, see `Chip8::with_random_recording`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum RandomLog {
+    /// Every byte the bus produced, in order.
+    Recording(Vec<u8>),
+    /// Bytes from an earlier recording, handed out in order starting at `next`.
+    Replaying { bytes: Vec<u8>, next: usize },
+}
+
+impl<B: Bus> Chip8<B> {
+    /// Keep every random byte `CXNN` uses, so a session can be reproduced later with
+    /// `with_random_replay`. The bytes are in `recorded_random_bytes`.
+    pub fn with_random_recording(mut self) -> Self {
+        self.random_log = Some(RandomLog::Recording(Vec::new()));
+        self
+    }
+
+    /// Feed `CXNN` the bytes of an earlier recording, in order, instead of asking the bus. The
+    /// seed makes no difference then. Once the recording runs out, the bus takes over again.
+    pub fn with_random_replay(mut self, bytes: Vec<u8>) -> Self {
+        self.random_log = Some(RandomLog::Replaying { bytes, next: 0 });
+        self
+    }
+
+    /// The random bytes recorded so far, empty unless `with_random_recording` is on.
+    pub fn recorded_random_bytes(&self) -> &[u8] {
+        match &self.random_log {
+            Some(RandomLog::Recording(bytes)) => bytes,
+            _ => &[],
+        }
+    }
+
+    /// How far into the log recording or replaying has got, for rewinding to it later.
+    pub(crate) fn random_position(&self) -> Option<usize> {
+        match &self.random_log {
+            Some(RandomLog::Recording(bytes)) => Some(bytes.len()),
+            Some(RandomLog::Replaying { next, .. }) => Some(*next),
+            None => None,
+        }
+    }
+
+    /// Go back to a `random_position`, forgetting what was recorded since.
+    pub(crate) fn rewind_random_to(&mut self, position: usize) {
+        match &mut self.random_log {
+            Some(RandomLog::Recording(bytes)) => bytes.truncate(position),
+            Some(RandomLog::Replaying { next, .. }) => *next = position,
+            None => {},
+        }
+    }
+
+    /// The next random byte for `CXNN`.
+    pub(crate) fn next_random_byte(&mut self) -> u8 {
+        match &mut self.random_log {
+            Some(RandomLog::Replaying { bytes, next }) if *next < bytes.len() => {
+                *next += 1;
+                bytes[*next - 1]
+            },
+            Some(RandomLog::Replaying { bytes, next }) if *next == bytes.len() => {
+                log::warn!("The random replay ran out after {} bytes; new random bytes from here on.", bytes.len());
+                *next += 1;
+                self.bus.random_byte()
+            },
+            Some(RandomLog::Recording(bytes)) => {
+                let byte = self.bus.random_byte();
+                bytes.push(byte);
+                byte
+            },
+            _ => self.bus.random_byte(),
+        }
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use super::super::Chip8Error;
+
+    const ROM: [u8; 8] = [
+        0xC0, 0xFF, // V0 = random
+        0xC1, 0x0F, // V1 = random & 0x0F
+        0xC2, 0xF0, // V2 = random & 0xF0
+        0x12, 0x00, // again
+    ];
+
+    #[test]
+    fn replaying_a_recording_reproduces_the_registers() -> Result<(), Chip8Error> {
+        let mut recorded = Chip8::from_rom_bytes(&ROM)?.with_rng_seed(3).with_random_recording();
+        recorded.run_cycles(40)?;
+        assert_eq!(recorded.recorded_random_bytes().len(), 30);
+
+        // A different seed makes no difference to a replay.
+        let mut replayed = Chip8::from_rom_bytes(&ROM)?
+            .with_rng_seed(99)
+            .with_random_replay(recorded.recorded_random_bytes().to_vec());
+        replayed.run_cycles(40)?;
+        assert_eq!(replayed.save_state_bytes(), recorded.save_state_bytes());
+        assert!(replayed.recorded_random_bytes().is_empty());
+        Ok(())
+    }
+}
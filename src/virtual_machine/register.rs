@@ -1,58 +1,91 @@
-use std::{num::ParseIntError, ops::{DerefMut, Index, IndexMut, Deref}};
+use core::{num::ParseIntError, ops::{DerefMut, Index, IndexMut, Deref}};
 
-use crate::data_structures::HexKeyMap;
+use alloc::{string::String, vec, vec::Vec};
+
+#[cfg(feature = "std")]
+use std::sync::{Arc, Mutex};
+
+#[cfg(feature = "std")]
 use thiserror::Error;
-use crossterm::event;
 
+use super::{InputSource, KeyEvent};
 
-#[derive(Debug, Error)]
+
+#[derive(Debug)]
+#[cfg_attr(feature = "std", derive(Error))]
 pub enum RegisterError {
-    #[error("Unknown register identifier provided where a single word was expected (i.e. [0-F]).")]
-    UnknownIdentifier(String),
-    #[error("")]
+    #[cfg_attr(feature = "std", error("Unknown register identifier `{0}`: expected a single hex digit (i.e. [0-F])."))]
+    UnknownIdentifier(char),
+    #[cfg_attr(feature = "std", error("Register index `{0}` is out of bounds: there are only 16 registers."))]
+    RegisterIndexOutOfBounds(usize),
+    #[cfg_attr(feature = "std", error("Unable to parse a register index: {0}"))]
+    Parse(ParseIntError),
+    #[cfg_attr(feature = "std", error(""))]
     AddressValueLargerThan12Bytes,
-    #[error("Register index out of bounds.")]
-    RegisterIndexOutOfBounds
 }
 
-#[derive(Debug, Error)]
+#[derive(Debug)]
+#[cfg_attr(feature = "std", derive(Error))]
+pub enum ScreenError {
+    #[cfg_attr(feature = "std", error("Row {row} is {found} pixels wide, but the other rows say otherwise."))]
+    WrongRowLength { row: usize, found: usize },
+    #[cfg_attr(feature = "std", error("Expected at least one row of pixels, found {0} rows."))]
+    WrongRowCount(usize),
+    #[cfg_attr(feature = "std", error("Expected {expected} bytes of packed pixels, found {found}."))]
+    WrongByteCount { expected: usize, found: usize },
+    #[cfg_attr(feature = "std", error("A {width}x{height} display is not supported: each side must be 1 to 255 pixels, and the pixels must fill whole bytes."))]
+    UnsupportedSize { width: usize, height: usize },
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "std", derive(Error))]
 pub enum KeyError {
-    #[error("Unable to recognize key input: `{0}`")]
+    #[cfg_attr(feature = "std", error("Unable to recognize key input: `{0}`"))]
     UnknownKeyInput(char)
 }
 
 
 impl From<ParseIntError> for RegisterError {
     fn from(err: ParseIntError) -> Self {
-        Self::UnknownIdentifier(err.to_string())
+        Self::Parse(err)
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+/// The index of the register named by the hex digit `identifier`.
+fn register_index(identifier: char) -> Result<usize, RegisterError> {
+    identifier
+        .to_digit(16)
+        .map(|index| index as usize)
+        .ok_or(RegisterError::UnknownIdentifier(identifier))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct DataRegisters([u8; 16]);
 
 impl DataRegisters {
 
     pub fn write(&mut self, register_identifier: char, value: u8) -> Result<u8, RegisterError> {
-        let index = usize::from_str_radix(&String::from(register_identifier), 16)?;
-        self.write_idx(index, value)
+        self.write_idx(register_index(register_identifier)?, value)
     }
 
     pub fn read(&self, register_identifier: char) -> Result<u8, RegisterError> {
-        let index = usize::from_str_radix(&String::from(register_identifier), 16)?;
-        self.read_idx(index)
+        self.read_idx(register_index(register_identifier)?)
     }
 
     pub fn read_idx(&self, register_idx: usize) -> Result<u8, RegisterError> {
         if register_idx >= 16 {
-            return Err(RegisterError::RegisterIndexOutOfBounds);
+            return Err(RegisterError::RegisterIndexOutOfBounds(register_idx));
         }
         Ok(self.0[register_idx])
     }
 
+    pub fn values(&self) -> [u8; 16] {
+        self.0
+    }
+
     pub fn write_idx(&mut self, register_idx: usize, value: u8) -> Result<u8, RegisterError> {
         if register_idx >= 16 {
-            return Err(RegisterError::RegisterIndexOutOfBounds);
+            return Err(RegisterError::RegisterIndexOutOfBounds(register_idx));
         }
         let previous_value = self.0[register_idx];
         self.0[register_idx] = value;
@@ -68,12 +101,63 @@ impl Default for DataRegisters {
     }
 }
 
+impl core::fmt::Display for DataRegisters {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for (idx, value) in self.0.iter().enumerate() {
+            if idx > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "V{:X}={:02x}", idx, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// A copy of the CPU registers at one point in time, cheap enough to take every frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterSnapshot {
+    pub data_registers: DataRegisters,
+    pub address_register: u16,
+    pub program_counter: u16,
+    pub stack_pointer: u16,
+}
+
 #[derive(Debug, Clone)]
-pub struct AddressRegister(u16);
+pub struct AddressRegister {
+    value: u16,
+    width: AddressWidth,
+}
+
+/// How many bits of an address register are kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AddressWidth {
+    /// CHIP-8 and SUPER-CHIP address 4K, so anything past 0x0FFF wraps.
+    #[default]
+    Bits12,
+    /// XO-CHIP lets I reach all of 64K.
+    Bits16,
+}
+
+impl AddressWidth {
+    pub fn mask(&self) -> u16 {
+        match self {
+            Self::Bits12 => 0x0FFF,
+            Self::Bits16 => 0xFFFF,
+        }
+    }
+}
 
 impl AddressRegister {
+    pub fn with_width(width: AddressWidth) -> Self {
+        Self { value: 0, width }
+    }
+
+    pub fn width(&self) -> AddressWidth {
+        self.width
+    }
+
     pub fn write(&mut self, value: u16) -> Result<(), RegisterError> {
-        self.0 = value & (0x0FFFu16);
+        self.value = value & self.width.mask();
         Ok(())
     }
     pub fn step(&mut self, size: usize) -> Result<(), RegisterError> {
@@ -82,13 +166,13 @@ impl AddressRegister {
     }
 
     pub fn read(&self) -> u16 {
-        self.0
+        self.value
     }
 }
 
 impl Default for AddressRegister {
     fn default() -> Self {
-        Self(0)
+        Self::with_width(AddressWidth::Bits12)
     }
 }
 
@@ -111,64 +195,441 @@ impl DerefMut for ProgramCounter {
 
 impl Default for ProgramCounter {
     fn default() -> Self {
-        Self(AddressRegister(0x200))
+        Self(AddressRegister { value: 0x200, width: AddressWidth::Bits12 })
     }
 }
 
-impl std::fmt::Display for ProgramCounter {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for ProgramCounter {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "{:04x}", self.read())
     }
 }
 
+/// Resolution of the original CHIP-8 display.
+pub const LORES: (usize, usize) = (64, 32);
+/// Resolution of SUPER-CHIP's high resolution mode.
+pub const HIRES: (usize, usize) = (128, 64);
+
+/// The display a machine starts out with, and returns to when SUPER-CHIP's `00FE` leaves hires mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisplayConfig {
+    pub width: usize,
+    pub height: usize,
+}
+
+impl DisplayConfig {
+    /// The original 64x32 display.
+    pub const CHIP8: Self = Self { width: LORES.0, height: LORES.1 };
+    /// The ETI-660's taller 64x48 display.
+    pub const ETI660: Self = Self { width: 64, height: 48 };
+    /// SUPER-CHIP's 128x64 display, from the start.
+    pub const HIRES: Self = Self { width: HIRES.0, height: HIRES.1 };
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        Self::CHIP8
+    }
+}
+
+impl From<DisplayConfig> for Screen {
+    fn from(config: DisplayConfig) -> Self {
+        Screen::new(config.width, config.height)
+    }
+}
+
+/// How `Screen::draw_sprite` combines a sprite with what's already on the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DrawMode {
+    /// Flip the pixels under the sprite's set bits, like DXYN. The only mode with collisions.
+    #[default]
+    Xor,
+    /// Turn on the pixels under the sprite's set bits.
+    Or,
+    /// Turn off the pixels under the sprite's clear bits, keeping only what the sprite covers.
+    And,
+}
+
+/// A monochrome framebuffer, stored row-major.
 #[derive(Debug)]
-pub struct Screen([bool; 64 * 32]);
+pub struct Screen {
+    pixels: Vec<bool>,
+    width: usize,
+    height: usize,
+    /// Set by the whole-screen utilities like `invert`, so the machine knows to redraw.
+    dirty: bool,
+    /// A buffer outside the machine that gets a copy of every change, see `Screen::with_buffer`.
+    #[cfg(feature = "std")]
+    external: Option<Arc<Mutex<Vec<bool>>>>,
+}
+
+/// A clone is a snapshot: it doesn't write into the buffer given to `Screen::with_buffer`.
+impl Clone for Screen {
+    fn clone(&self) -> Self {
+        Self {
+            pixels: self.pixels.clone(),
+            width: self.width,
+            height: self.height,
+            dirty: self.dirty,
+            #[cfg(feature = "std")]
+            external: None,
+        }
+    }
+}
+
+/// Screens are equal when they show the same picture, whether or not it was drawn yet.
+impl PartialEq for Screen {
+    fn eq(&self, other: &Self) -> bool {
+        (self.width, self.height) == (other.width, other.height) && self.pixels == other.pixels
+    }
+}
+
+impl Eq for Screen {}
+
+/// The pixels that changed between two frames, as `(x, y)` coordinates in row-major order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FrameDiff {
+    pub turned_on: Vec<(usize, usize)>,
+    pub turned_off: Vec<(usize, usize)>,
+}
+
+impl FrameDiff {
+    /// How many pixels changed either way.
+    pub fn count(&self) -> usize {
+        self.turned_on.len() + self.turned_off.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count() == 0
+    }
+}
 
 impl Default for Screen {
     fn default() -> Self {
-        Self([false; 64 * 32])
+        Self::new(LORES.0, LORES.1)
     }
 }
 
 impl Screen {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            pixels: vec![false; width * height],
+            width,
+            height,
+            dirty: false,
+            #[cfg(feature = "std")]
+            external: None,
+        }
+    }
+
+    /// A `width` x `height` screen whose pixels are also written to `buffer`, e.g. the one an LED
+    /// matrix driver reads from. Every draw, clear, scroll, `set` and resolution change lands in it
+    /// before returning; a sprite only writes the pixels it covers. Pixels set by indexing land on
+    /// the next whole-screen change, or on `flush`. Clones of this screen don't write to `buffer`.
+    #[cfg(feature = "std")]
+    pub fn with_buffer(buffer: Arc<Mutex<Vec<bool>>>, width: usize, height: usize) -> Self {
+        let mut screen = Self::new(width, height);
+        screen.external = Some(buffer);
+        screen.flush();
+        screen
+    }
+
+    /// Turns the pixel at `index` on or off, writing it through to the buffer given to
+    /// `with_buffer` right away.
+    pub fn set(&mut self, index: usize, on: bool) {
+        self.pixels[index] = on;
+        #[cfg(feature = "std")]
+        if let Some(external) = &self.external {
+            lock_buffer(external, &self.pixels)[index] = on;
+        }
+    }
+
+    /// Copies the pixels into the buffer given to `with_buffer`, if any.
+    pub fn flush(&self) {
+        #[cfg(feature = "std")]
+        if let Some(external) = &self.external {
+            let mut external = external.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            external.clear();
+            external.extend_from_slice(&self.pixels);
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Number of pixels, i.e. `width * height`.
+    pub fn len(&self) -> usize {
+        self.pixels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pixels.is_empty()
+    }
+
+    /// Whether the screen is in SUPER-CHIP's 128x64 mode.
+    pub fn is_hires(&self) -> bool {
+        (self.width, self.height) == HIRES
+    }
+
+    /// Switches between the 64x32 and 128x64 resolutions. Like on the HP48, this clears the screen.
+    pub fn set_hires(&mut self, hires: bool) {
+        let (width, height) = if hires { HIRES } else { LORES };
+        self.resize(width, height);
+    }
+
+    /// Switches to a `width` x `height` display, and clears it.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        self.pixels = vec![false; width * height];
+        (self.width, self.height) = (width, height);
+        self.dirty = false;
+        self.flush();
+    }
+
     pub fn clear(&mut self) {
-        for i in 0usize..(64 * 32) {
-            self.0[i] = false;
+        self.pixels.fill(false);
+        self.flush();
+    }
+
+    /// Turns every pixel on or off, and flags a redraw.
+    pub fn fill(&mut self, on: bool) {
+        self.pixels.fill(on);
+        self.dirty = true;
+        self.flush();
+    }
+
+    /// Flips every pixel, and flags a redraw.
+    pub fn invert(&mut self) {
+        for pixel in &mut self.pixels {
+            *pixel = !*pixel;
         }
+        self.dirty = true;
+        self.flush();
     }
-}
 
-impl std::fmt::Display for Screen {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        const NUM_ROWS: usize = 32;
-        const NUM_COLS: usize = 64;
+    /// Moves everything down by `rows`. Rows scrolled in at the top are off, and the screen is
+    /// flagged for a redraw. SUPER-CHIP's `00CN`.
+    pub fn scroll_down(&mut self, rows: usize) {
+        let shift = rows.min(self.height) * self.width;
+        self.pixels.rotate_right(shift);
+        self.pixels[..shift].fill(false);
+        self.dirty = true;
+        self.flush();
+    }
 
-        let mut s = String::from("");
+    /// Moves everything up by `rows`, filling in off rows at the bottom. XO-CHIP's `00DN`.
+    pub fn scroll_up(&mut self, rows: usize) {
+        let shift = rows.min(self.height) * self.width;
+        self.pixels.rotate_left(shift);
+        let len = self.pixels.len();
+        self.pixels[len - shift..].fill(false);
+        self.dirty = true;
+        self.flush();
+    }
 
-        for row_idx in 0..NUM_ROWS {
-            for col_idx in 0..NUM_COLS {
-                let coordinate = NUM_ROWS * row_idx + col_idx;
-                s += &format!("{:08b}", if self.0[coordinate] { 1 } else { 0 });
+    /// Moves everything left by `columns`, filling in off pixels on the right. SUPER-CHIP's `00FC`.
+    pub fn scroll_left(&mut self, columns: usize) {
+        let shift = columns.min(self.width);
+        for row in self.pixels.chunks_mut(self.width) {
+            row.rotate_left(shift);
+            let width = row.len();
+            row[width - shift..].fill(false);
+        }
+        self.dirty = true;
+        self.flush();
+    }
+
+    /// Moves everything right by `columns`, filling in off pixels on the left. SUPER-CHIP's `00FB`.
+    pub fn scroll_right(&mut self, columns: usize) {
+        let shift = columns.min(self.width);
+        for row in self.pixels.chunks_mut(self.width) {
+            row.rotate_right(shift);
+            row[..shift].fill(false);
+        }
+        self.dirty = true;
+        self.flush();
+    }
+
+    /// Whether `fill`, `invert` or a scroll changed the screen since the last call. Clears the flag.
+    pub fn take_dirty(&mut self) -> bool {
+        core::mem::take(&mut self.dirty)
+    }
+
+    /// Combines a `width` x `height` sprite with the screen according to `mode`, with its top-left
+    /// corner at (`x`, `y`). Each row is `width / 8` bytes, most significant bit on the left. The
+    /// corner wraps around the screen, but the parts of the sprite that run off the right or bottom
+    /// edge are clipped. In `DrawMode::Xor` returns whether any pixel was turned off, i.e. whether
+    /// there was a collision; the other modes never collide.
+    pub fn draw_sprite(&mut self, x: u8, y: u8, width: u8, height: u8, sprite: &[u8], mode: DrawMode) -> bool {
+        self.blit(x, y, width, height, sprite, mode, false)
+    }
+
+    /// Like `draw_sprite`, but the parts that run off an edge reappear on the other side.
+    pub fn draw_sprite_wrapping(&mut self, x: u8, y: u8, width: u8, height: u8, sprite: &[u8], mode: DrawMode) -> bool {
+        self.blit(x, y, width, height, sprite, mode, true)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn blit(&mut self, x: u8, y: u8, width: u8, height: u8, sprite: &[u8], mode: DrawMode, wrap: bool) -> bool {
+        if width == 0 || height == 0 {
+            return false;
+        }
+        let origin_x = x as usize % self.width;
+        let origin_y = y as usize % self.height;
+        let bytes_per_row = (width as usize).div_ceil(8);
+        let mut collision = false;
+        #[cfg(feature = "std")]
+        let mut external = self.external.as_ref().map(|buffer| lock_buffer(buffer, &self.pixels));
+
+        for (yline, row) in sprite.chunks(bytes_per_row).take(height as usize).enumerate() {
+            let mut y = origin_y + yline;
+            if y >= self.height {
+                if !wrap {
+                    break;
+                }
+                y %= self.height;
             }
-            s += "\n";
+
+            for xline in 0..width as usize {
+                let mut x = origin_x + xline;
+                if x >= self.width {
+                    if !wrap {
+                        break;
+                    }
+                    x %= self.width;
+                }
+
+                // A short last row is padded with zeros.
+                let set = row.get(xline / 8).copied().unwrap_or(0) & (0x80 >> (xline % 8)) != 0;
+                let index = x + y * self.width;
+                match mode {
+                    DrawMode::Xor if set => {
+                        collision |= self.pixels[index];
+                        self.pixels[index] = !self.pixels[index];
+                    },
+                    DrawMode::Or if set => self.pixels[index] = true,
+                    DrawMode::And if !set => self.pixels[index] = false,
+                    _ => {},
+                }
+                #[cfg(feature = "std")]
+                if let Some(external) = external.as_mut() {
+                    external[index] = self.pixels[index];
+                }
+            }
+        }
+        collision
+    }
+
+    /// Lists the pixels that differ from `prev`. If `prev` has another resolution, e.g. across
+    /// a switch in or out of hires mode, nothing lines up and every lit pixel is turned on.
+    pub fn diff(&self, prev: &Screen) -> FrameDiff {
+        let mut diff = FrameDiff::default();
+        let same_size = (self.width, self.height) == (prev.width, prev.height);
+        for (index, now) in self.pixels.iter().enumerate() {
+            let before = same_size && prev.pixels[index];
+            let coordinate = (index % self.width, index / self.width);
+            match (before, *now) {
+                (false, true) => diff.turned_on.push(coordinate),
+                (true, false) => diff.turned_off.push(coordinate),
+                _ => {}
+            }
+        }
+        diff
+    }
+
+    /// Renders the screen as one line of characters per row, separated by `\n`.
+    pub fn to_ascii(&self, on: char, off: char) -> String {
+        let mut s = String::with_capacity((self.width + 1) * self.height);
+        for (row_idx, row) in self.pixels.chunks(self.width).enumerate() {
+            if row_idx > 0 {
+                s.push('\n');
+            }
+            s.extend(row.iter().map(|pixel| if *pixel { on } else { off }));
+        }
+        s
+    }
+
+    /// Packs the pixels row by row into bytes, eight to a byte with the leftmost pixel in the
+    /// most significant bit. A row that isn't a multiple of eight wide runs on into the next byte.
+    pub fn pack_1bpp(&self) -> Vec<u8> {
+        let mut bytes = vec![0u8; self.pixels.len().div_ceil(8)];
+        for (index, pixel) in self.pixels.iter().enumerate() {
+            if *pixel {
+                bytes[index / 8] |= 0x80 >> (index % 8);
+            }
+        }
+        bytes
+    }
+
+    /// The inverse of `pack_1bpp` for a `width` x `height` screen.
+    pub fn unpack_1bpp(bytes: &[u8], width: usize, height: usize) -> Result<Screen, ScreenError> {
+        let mut screen = Screen::new(width, height);
+        let expected = screen.pixels.len().div_ceil(8);
+        if bytes.len() != expected {
+            return Err(ScreenError::WrongByteCount { expected, found: bytes.len() });
+        }
+        for (index, pixel) in screen.pixels.iter_mut().enumerate() {
+            *pixel = bytes[index / 8] & (0x80 >> (index % 8)) != 0;
         }
-        s = s.replace("0", " ");
-        s = s.replace("1", "*");
+        Ok(screen)
+    }
 
-        writeln!(f, "{}", s)
+    /// Parses the output of `to_ascii`, at whatever size it was. The first row sets the width
+    /// and the number of rows the height. Any character other than `on` is an unset pixel.
+    pub fn from_ascii(text: &str, on: char) -> Result<Screen, ScreenError> {
+        let text = text.strip_suffix('\n').unwrap_or(text);
+        if text.is_empty() {
+            return Err(ScreenError::WrongRowCount(0));
+        }
+        let rows: Vec<&str> = text.split('\n').collect();
+        let (width, height) = (rows[0].chars().count(), rows.len());
+
+        let mut screen = Screen::new(width, height);
+        for (row_idx, row) in rows.iter().enumerate() {
+            let found = row.chars().count();
+            if found != width {
+                return Err(ScreenError::WrongRowLength { row: row_idx, found });
+            }
+            for (col_idx, pixel) in row.chars().enumerate() {
+                screen.pixels[row_idx * width + col_idx] = pixel == on;
+            }
+        }
+        Ok(screen)
+    }
+}
+
+/// Locks the buffer given to `Screen::with_buffer`, bringing it back in line with `pixels`
+/// if something else changed its length.
+#[cfg(feature = "std")]
+fn lock_buffer<'a>(buffer: &'a Mutex<Vec<bool>>, pixels: &[bool]) -> std::sync::MutexGuard<'a, Vec<bool>> {
+    let mut external = buffer.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if external.len() != pixels.len() {
+        external.clear();
+        external.extend_from_slice(pixels);
+    }
+    external
+}
+
+impl core::fmt::Display for Screen {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        writeln!(f, "{}", self.to_ascii('*', ' '))
     }
 }
 
 impl Index<usize> for Screen {
     type Output = bool;
     fn index(&self, index: usize) -> &Self::Output {
-        self.0.index(index)
+        self.pixels.index(index)
     }
 }
 
 impl IndexMut<usize> for Screen {
     fn index_mut(&mut self, index: usize) -> &mut Self::Output {
-        self.0.index_mut(index)
+        self.pixels.index_mut(index)
     }
 }
 
@@ -194,46 +655,336 @@ pub type Stack = [u16; 16];
 
 pub type StackPointer = u16;
 
-#[derive(Debug)]
+/// Which of the 16 keys are held. Key state only changes through the methods here, typically
+/// `apply_input` with whatever `InputSource` the host reads from.
+#[derive(Debug, Default)]
 pub struct Keypad {
     _inner: [bool; 16],
-    pub keymap: HexKeyMap,
-}
-
-impl Default for Keypad {
-    fn default() -> Self {
-        Self { 
-            _inner: [false; 16],
-            keymap: HexKeyMap::default()
-        }
-    }
 }
 
 impl Keypad {
-    pub fn press(&mut self, key_identifier: char) {
-        if let Ok(index) = usize::from_str_radix(&String::from(key_identifier), 16) {
-            self._inner[index] = true;
-        }
+    /// Marks the key named by a hex digit as pressed.
+    pub fn press(&mut self, key_identifier: char) -> Result<(), KeyError> {
+        self._inner[Self::key_index(key_identifier)?] = true;
+        Ok(())
     }
-    pub fn unpress(&mut self, key_identifier: char) {
-        if let Ok(index) = usize::from_str_radix(&String::from(key_identifier), 16) {
-            self._inner[index] = false;
-        }
+    pub fn unpress(&mut self, key_identifier: char) -> Result<(), KeyError> {
+        self._inner[Self::key_index(key_identifier)?] = false;
+        Ok(())
+    }
+    /// Presses or releases a key by number. Only the low nibble counts, like for `is_pressed`.
+    pub fn set(&mut self, key: u8, pressed: bool) {
+        self._inner[(key & 0x0Fu8) as usize] = pressed;
+    }
+    fn key_index(key_identifier: char) -> Result<usize, KeyError> {
+        key_identifier
+            .to_digit(16)
+            .map(|digit| digit as usize)
+            .ok_or(KeyError::UnknownKeyInput(key_identifier))
     }
     pub fn is_pressed(&self, key: u8) -> bool {
         let key = (key & 0x0Fu8) as usize;
         self._inner[key]
     }
+    /// The lowest key that is held, if any.
     pub fn read(&mut self) -> Option<u8> {
-        match event::read() {
-            Ok(event::Event::Key(k)) => {
-                if let Some(mapped_value) = self.keymap.0.get(&k.code) {
-                    return Some(*mapped_value as u8);
+        self._inner.iter().position(|pressed| *pressed).map(|index| index as u8)
+    }
+
+    /// Apply everything `input` has seen since it was last polled. Returns `false` if that
+    /// included a request to quit.
+    pub fn apply_input<I: InputSource + ?Sized>(&mut self, input: &mut I) -> bool {
+        let mut keep_running = true;
+        for event in input.poll() {
+            match event {
+                KeyEvent::Pressed(key) => self.set(key, true),
+                KeyEvent::Released(key) => self.set(key, false),
+                KeyEvent::Quit => keep_running = false,
+            }
+        }
+        keep_running
+    }
+
+    /// Whether each of the keys 0x0 to 0xF is held.
+    pub fn state(&self) -> [bool; 16] {
+        self._inner
+    }
+}
+
+/// The keys as laid out on the COSMAC VIP's hex keypad.
+const KEYPAD_LAYOUT: [[u8; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
+/// Draws the keypad as a 4x4 grid, with held keys in brackets, e.g. `[1] 2  3  C `.
+impl core::fmt::Display for Keypad {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for (row_idx, row) in KEYPAD_LAYOUT.iter().enumerate() {
+            if row_idx > 0 {
+                writeln!(f)?;
+            }
+            for key in row {
+                if self.is_pressed(*key) {
+                    write!(f, "[{:X}]", key)?;
+                } else {
+                    write!(f, " {:X} ", key)?;
                 }
-            },
-            _ => {
             }
         }
-        None
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn display_data_registers() -> Result<(), RegisterError> {
+        let mut registers = DataRegisters::default();
+        registers.write('0', 0x2A)?;
+        registers.write('9', 0x07)?;
+        registers.write('F', 0x01)?;
+
+        assert_eq!(
+            registers.to_string(),
+            "V0=2a V1=00 V2=00 V3=00 V4=00 V5=00 V6=00 V7=00 \
+             V8=00 V9=07 VA=00 VB=00 VC=00 VD=00 VE=00 VF=01"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn drawing_a_sprite_twice_erases_it() {
+        let mut screen = Screen::default();
+        let sprite = [0b1100_0011, 0b0011_1100];
+
+        assert!(!screen.draw_sprite(10, 4, 8, 2, &sprite, DrawMode::Xor));
+        assert!(screen[4 * 64 + 10] && screen[4 * 64 + 17] && !screen[4 * 64 + 12]);
+        assert!(screen[5 * 64 + 12] && screen[5 * 64 + 15]);
+
+        assert!(screen.draw_sprite(10, 4, 8, 2, &sprite, DrawMode::Xor));
+        assert_eq!(screen, Screen::default());
+    }
+
+    #[test]
+    fn drawing_a_sprite_twice_in_or_mode_keeps_it() {
+        let mut screen = Screen::default();
+        let sprite = [0b1100_0011, 0b0011_1100];
+
+        assert!(!screen.draw_sprite(10, 4, 8, 2, &sprite, DrawMode::Or));
+        let once = screen.clone();
+        assert!(!screen.draw_sprite(10, 4, 8, 2, &sprite, DrawMode::Or));
+        assert_eq!(screen, once);
+        assert!(screen[4 * 64 + 10] && screen[5 * 64 + 12]);
+
+        // AND keeps only the pixels the sprite covers.
+        screen.fill(true);
+        assert!(!screen.draw_sprite(10, 4, 8, 2, &sprite, DrawMode::And));
+        assert!(screen[4 * 64 + 10] && !screen[4 * 64 + 12] && screen[5 * 64 + 12]);
+        assert!(screen[3 * 64 + 12] && screen[4 * 64 + 20]);
+    }
+
+    #[test]
+    fn sprites_clip_or_wrap_at_the_edges() {
+        let mut clipped = Screen::default();
+        clipped.draw_sprite(60, 31, 8, 2, &[0xFF, 0xFF], DrawMode::Xor);
+        assert!(clipped[31 * 64 + 63] && !clipped[31 * 64] && !clipped[63]);
+
+        let mut wrapped = Screen::default();
+        wrapped.draw_sprite_wrapping(60, 31, 8, 2, &[0xFF, 0xFF], DrawMode::Xor);
+        assert!(wrapped[31 * 64 + 63] && wrapped[31 * 64 + 3] && wrapped[63] && wrapped[3]);
+
+        // The corner itself always wraps.
+        let mut screen = Screen::default();
+        screen.draw_sprite(64 + 1, 32 + 2, 8, 1, &[0x80], DrawMode::Xor);
+        assert!(screen[2 * 64 + 1]);
+    }
+
+    #[test]
+    fn short_or_empty_sprites_draw_what_they_have() {
+        let mut screen = Screen::default();
+        assert!(!screen.draw_sprite(0, 0, 16, 1, &[0xFF], DrawMode::Xor));
+        assert!((0..8).all(|x| screen[x]) && !(8..16).any(|x| screen[x]));
+
+        let mut screen = Screen::default();
+        assert!(!screen.draw_sprite(0, 0, 0, 4, &[0xFF; 4], DrawMode::Xor));
+        assert!(!screen.draw_sprite_wrapping(0, 0, 8, 0, &[0xFF], DrawMode::Xor));
+        assert_eq!(screen, Screen::default());
+    }
+
+    #[test]
+    fn register_errors_name_the_culprit() {
+        let mut registers = DataRegisters::default();
+
+        let err = registers.read('G').unwrap_err();
+        assert!(matches!(err, RegisterError::UnknownIdentifier('G')));
+        #[cfg(feature = "std")]
+        assert!(err.to_string().contains("`G`"));
+
+        assert!(matches!(registers.write_idx(16, 0), Err(RegisterError::RegisterIndexOutOfBounds(16))));
+    }
+
+    #[test]
+    fn keypad_follows_programmatic_input() {
+        use crate::virtual_machine::ProgrammaticInput;
+
+        let mut keypad = Keypad::default();
+        let mut input = ProgrammaticInput::default();
+        input.push(KeyEvent::Pressed(0x7));
+        input.push(KeyEvent::Pressed(0xC));
+        input.push(KeyEvent::Released(0x7));
+        assert!(keypad.apply_input(&mut input));
+        assert!(!keypad.is_pressed(0x7));
+        assert!(keypad.is_pressed(0xC));
+        assert_eq!(keypad.read(), Some(0xC));
+
+        input.push(KeyEvent::Quit);
+        assert!(!keypad.apply_input(&mut input));
+        assert!(keypad.apply_input(&mut input));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn pressing_a_non_hex_key_is_an_error() {
+        let mut keypad = Keypad::default();
+        assert!(matches!(keypad.press('g'), Err(KeyError::UnknownKeyInput('g'))));
+        assert!(matches!(keypad.unpress('!'), Err(KeyError::UnknownKeyInput('!'))));
+        assert!(keypad.state().iter().all(|pressed| !pressed));
+
+        assert!(keypad.press('a').is_ok());
+        assert!(keypad.is_pressed(0xA));
+    }
+
+    #[test]
+    fn keypad_state_and_grid() {
+        let mut keypad = Keypad::default();
+        keypad.press('A').unwrap();
+        keypad.press('3').unwrap();
+
+        let state = keypad.state();
+        assert!(state[0xA] && state[0x3]);
+        assert_eq!(state.iter().filter(|pressed| **pressed).count(), 2);
+        assert_eq!(
+            keypad.to_string(),
+            " 1  2 [3] C \n 4  5  6  D \n 7  8  9  E \n[A] 0  B  F "
+        );
+    }
+
+    #[test]
+    fn fill_then_invert_is_all_off() {
+        let mut screen = Screen::default();
+        screen.fill(true);
+        assert!((0..screen.len()).all(|pixel| screen[pixel]));
+
+        screen.invert();
+        assert!((0..screen.len()).all(|pixel| !screen[pixel]));
+        assert!(screen.take_dirty());
+        assert!(!screen.take_dirty());
+    }
+
+    #[test]
+    fn scroll_down_and_right() -> Result<(), ScreenError> {
+        let pattern = |rows: &[(usize, &str)]| {
+            let mut text = vec![".".repeat(64); 32];
+            for (y, row) in rows {
+                text[*y].replace_range(..row.len(), row);
+            }
+            Screen::from_ascii(&text.join("\n"), '#')
+        };
+        let mut screen = pattern(&[(0, "##.#"), (1, ".##"), (31, "#")])?;
+
+        screen.scroll_down(2);
+        assert_eq!(screen, pattern(&[(2, "##.#"), (3, ".##")])?);
+        assert!(screen.take_dirty());
+
+        screen.scroll_right(4);
+        assert_eq!(screen, pattern(&[(2, "....##.#"), (3, ".....##")])?);
+
+        screen.scroll_left(5);
+        assert_eq!(screen, pattern(&[(2, "#.#"), (3, "##")])?);
+
+        screen.scroll_right(64);
+        assert_eq!(screen, Screen::default());
+        Ok(())
+    }
+
+    #[test]
+    fn screen_over_an_external_buffer() {
+        let buffer = Arc::new(Mutex::new(Vec::new()));
+        let mut screen = Screen::with_buffer(buffer.clone(), 64, 32);
+        assert_eq!(*buffer.lock().unwrap(), vec![false; 64 * 32]);
+
+        screen.draw_sprite(2, 1, 8, 1, &[0b1010_0000], DrawMode::Xor);
+        {
+            let external = buffer.lock().unwrap();
+            assert!(external[2 + 64] && !external[3 + 64] && external[4 + 64]);
+            assert_eq!(external.iter().filter(|pixel| **pixel).count(), 2);
+        }
+
+        screen[0] = true;
+        assert!(!buffer.lock().unwrap()[0]);
+        screen.flush();
+        assert!(buffer.lock().unwrap()[0]);
+        screen.set(1, true);
+        assert!(buffer.lock().unwrap()[1]);
+
+        // A clone is only a snapshot.
+        let mut snapshot = screen.clone();
+        snapshot.fill(true);
+        snapshot.draw_sprite(0, 8, 8, 1, &[0xFF], DrawMode::Xor);
+        snapshot.set(64 * 20, true);
+        assert_eq!(buffer.lock().unwrap().iter().filter(|pixel| **pixel).count(), 4);
+
+        screen.set_hires(true);
+        assert_eq!(*buffer.lock().unwrap(), vec![false; 128 * 64]);
+    }
+
+    #[test]
+    fn packed_1bpp_round_trip() -> Result<(), ScreenError> {
+        let mut screen = Screen::default();
+        screen.draw_sprite(3, 5, 8, 5, &[0xF0, 0x90, 0x90, 0x90, 0xF0], DrawMode::Xor);
+        screen.draw_sprite(60, 31, 8, 1, &[0xFF], DrawMode::Xor);
+
+        let packed = screen.pack_1bpp();
+        assert_eq!(packed.len(), 64 * 32 / 8);
+        assert_eq!(packed[5 * 8], 0b0001_1110);
+        assert_eq!(packed[255], 0b0000_1111);
+        assert_eq!(Screen::unpack_1bpp(&packed, 64, 32)?, screen);
+        assert!(matches!(
+            Screen::unpack_1bpp(&packed[1..], 64, 32),
+            Err(ScreenError::WrongByteCount { expected: 256, found: 255 })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn screen_ascii_round_trip() -> Result<(), ScreenError> {
+        let mut rows = vec![".".repeat(64); 32];
+        rows[0] = format!("#{}#", ".".repeat(62));
+        rows[15] = format!("{}####{}", ".".repeat(30), ".".repeat(30));
+        rows[31] = "#.".repeat(32);
+        let text = rows.join("\n");
+
+        let screen = Screen::from_ascii(&text, '#')?;
+        assert!(screen[0] && screen[63] && !screen[1]);
+        assert!(screen[15 * 64 + 30] && screen[15 * 64 + 33] && !screen[15 * 64 + 34]);
+        assert!(screen[31 * 64] && !screen[31 * 64 + 1]);
+        assert_eq!(screen.to_ascii('#', '.'), text);
+        assert_eq!(Screen::from_ascii(&screen.to_ascii('#', ' '), '#')?, screen);
+
+        assert!(matches!(Screen::from_ascii("", '#'), Err(ScreenError::WrongRowCount(0))));
+        let tiny = Screen::from_ascii("#.#\n.#.", '#')?;
+        assert_eq!((tiny.width(), tiny.height()), (3, 2));
+        assert!(tiny[0] && !tiny[1] && tiny[4]);
+        rows[3].push('.');
+        assert!(matches!(
+            Screen::from_ascii(&rows.join("\n"), '#'),
+            Err(ScreenError::WrongRowLength { row: 3, found: 65 })
+        ));
+        Ok(())
+    }
+}
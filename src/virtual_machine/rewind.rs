@@ -0,0 +1,169 @@
+use alloc::{collections::VecDeque, vec::Vec};
+
+use super::{Bus, Chip8, Chip8Error, OpLiteral};
+use super::tripwire::VfUse;
+
+
+/// The machine as it was just before one instruction ran.
+#[derive(Debug, Clone)]
+pub(crate) struct Snapshot {
+    /// See `Chip8::save_state_bytes`.
+    pub(crate) state: Vec<u8>,
+    pub(crate) cycles: u64,
+    /// Everything else an instruction can change, which a savestate leaves out.
+    pub(crate) rpl_flags: [u8; 8],
+    pub(crate) should_draw: bool,
+    pub(crate) fx0a_latched: Option<u8>,
+    pub(crate) max_stack_depth: usize,
+    pub(crate) max_memory_write: Option<u16>,
+    pub(crate) executed: [bool; OpLiteral::COUNT],
+    pub(crate) initialized: Option<Vec<bool>>,
+    pub(crate) vf_tripwire: Option<VfUse>,
+    pub(crate) random_position: Option<usize>,
+}
+
+/// The last few snapshots, oldest first. Once full, the oldest is dropped to make room.
+#[derive(Debug, Clone)]
+pub(crate) struct RewindBuffer {
+    pub(crate) snapshots: VecDeque<Snapshot>,
+    pub(crate) capacity: usize,
+}
+
+impl RewindBuffer {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self { snapshots: VecDeque::with_capacity(capacity), capacity }
+    }
+
+    pub(crate) fn push(&mut self, snapshot: Snapshot) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.snapshots.len() == self.capacity {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(snapshot);
+    }
+}
+
+impl<B: Bus> Chip8<B> {
+    /// Snapshot the machine before every instruction, keeping the last `capacity`
+    /// so that `step_back` can undo them.
+    pub fn with_rewind(mut self, capacity: usize) -> Self {
+        self.rewind = Some(RewindBuffer::new(capacity));
+        self
+    }
+
+    /// How many instructions `step_back` can currently undo.
+    pub fn rewind_len(&self) -> usize {
+        self.rewind.as_ref().map_or(0, |rewind| rewind.snapshots.len())
+    }
+
+    /// Undo the last instruction, restoring registers, memory, timers, the screen, the RPL flags
+    /// and the bookkeeping that goes with them, like coverage and the memory sanitizer, to what
+    /// they were right before it ran. Unlike `load_state_bytes`, the loaded program stays known.
+    pub fn step_back(&mut self) -> Result<(), Chip8Error> {
+        let snapshot = self.rewind
+            .as_mut()
+            .and_then(|rewind| rewind.snapshots.pop_back())
+            .ok_or(Chip8Error::RewindEmpty)?;
+
+        self.restore_state_bytes(&snapshot.state)?;
+        self.cycles = snapshot.cycles;
+        self.rpl_flags = snapshot.rpl_flags;
+        self.should_draw = snapshot.should_draw;
+        self.fx0a_latched = snapshot.fx0a_latched;
+        self.max_stack_depth = snapshot.max_stack_depth;
+        self.max_memory_write = snapshot.max_memory_write;
+        self.executed = snapshot.executed;
+        self.initialized = snapshot.initialized;
+        self.vf_tripwire = snapshot.vf_tripwire;
+        if let Some(position) = snapshot.random_position {
+            self.rewind_random_to(position);
+        }
+        // Undoing a write isn't one the program made, so watchpoints start over from here.
+        for (address, last) in &mut self.watchpoints {
+            *last = self.bus.read(*address as usize);
+        }
+        // Halted machines don't step, so nothing was halted before a recorded instruction.
+        self.halted = false;
+        Ok(())
+    }
+
+    pub(crate) fn record_snapshot(&mut self) {
+        if self.rewind.is_none() {
+            return;
+        }
+        let snapshot = Snapshot {
+            state: self.save_state_bytes(),
+            cycles: self.cycles,
+            rpl_flags: self.rpl_flags,
+            should_draw: self.should_draw,
+            fx0a_latched: self.fx0a_latched,
+            max_stack_depth: self.max_stack_depth,
+            max_memory_write: self.max_memory_write,
+            executed: self.executed,
+            initialized: self.initialized.clone(),
+            vf_tripwire: self.vf_tripwire,
+            random_position: self.random_position(),
+        };
+        if let Some(rewind) = &mut self.rewind {
+            rewind.push(snapshot);
+        }
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn step_back_undoes_the_last_instructions() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::from_rom_bytes(&[
+            0x60, 0x05, // V0 = 5
+            0xA3, 0x00, // I = 0x300
+            0xF0, 0x33, // BCD of V0 at I
+        ])?.with_rewind(8);
+
+        chip.step()?;
+        let after_first = chip.save_state_bytes();
+        chip.step()?;
+        chip.step()?;
+
+        chip.step_back()?;
+        chip.step_back()?;
+        assert_eq!(chip.save_state_bytes(), after_first);
+        assert_eq!(chip.cycles(), 1);
+        assert_eq!(chip.rewind_len(), 1);
+
+        chip.step_back()?;
+        assert!(matches!(chip.step_back(), Err(Chip8Error::RewindEmpty)));
+        Ok(())
+    }
+
+    #[test]
+    fn step_back_undoes_fx75_and_keeps_the_sanitizer() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::new().with_memory_sanitizer().with_rewind(8);
+        chip.initialize();
+        chip.load_program_bytes(&[
+            0x60, 0x2A, // V0 = 0x2A
+            0xF0, 0x75, // RPL flag 0 = V0
+            0x13, 0x00, // jump into memory nothing wrote
+        ]);
+        let sha1 = chip.rom_sha1();
+
+        chip.step()?;
+        chip.should_draw = false;
+        chip.step()?;
+        assert_eq!(chip.rpl_flags[0], 0x2A);
+        chip.step_back()?;
+        assert_eq!(chip.rpl_flags[0], 0);
+        assert!(!chip.should_draw);
+        assert!(!chip.executed[OpLiteral::_FX75 as usize]);
+        assert_eq!(chip.rom_sha1(), sha1);
+
+        // The sanitizer still catches the bad jump.
+        assert_eq!(chip.run_cycles(10)?, super::super::StopReason::UninitializedFetch(0x300));
+        Ok(())
+    }
+}
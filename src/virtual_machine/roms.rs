@@ -0,0 +1,48 @@
+/// ROMs compiled into the binary, so the emulator has something to run
+/// without any files on disk.
+pub struct BundledRoms;
+
+impl BundledRoms {
+    const ROMS: &'static [(&'static str, &'static [u8])] = &[
+        ("pong2", include_bytes!("../../programs/pong2.c8")),
+        ("tetris", include_bytes!("../../programs/tetris.c8")),
+    ];
+
+    /// Looks a ROM up by name, with or without the `.c8` extension.
+    pub fn get(name: &str) -> Option<&'static [u8]> {
+        let name = name.strip_suffix(".c8").unwrap_or(name);
+        Self::ROMS
+            .iter()
+            .find(|(rom_name, _)| *rom_name == name)
+            .map(|(_, bytes)| *bytes)
+    }
+
+    pub fn names() -> impl Iterator<Item = &'static str> {
+        Self::ROMS.iter().map(|(name, _)| *name)
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::virtual_machine::{Bus, Chip8};
+
+    #[test]
+    fn lookup_by_name() {
+        assert!(BundledRoms::get("pong2").is_some());
+        assert_eq!(BundledRoms::get("tetris.c8"), BundledRoms::get("tetris"));
+        assert!(BundledRoms::get("missing").is_none());
+        assert_eq!(BundledRoms::names().count(), 2);
+    }
+
+    #[test]
+    fn load_program_falls_back_to_bundled_rom() {
+        let mut chip = Chip8::new();
+        chip.load_program("pong2.c8");
+
+        let expected = BundledRoms::get("pong2").unwrap();
+        let loaded: Vec<u8> = (0..expected.len()).map(|idx| chip.bus().read(0x200 + idx)).collect();
+        assert_eq!(loaded, expected);
+    }
+}
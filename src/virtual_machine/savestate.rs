@@ -0,0 +1,248 @@
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use thiserror::Error;
+
+use super::{Bus, Chip8, Chip8Error, MEMORY_SIZE};
+
+
+/// Every savestate starts with these bytes, followed by the format version.
+pub const SAVESTATE_MAGIC: [u8; 4] = *b"C8SS";
+pub const SAVESTATE_VERSION: u8 = 2;
+
+/// magic, version, memory, V0-VF, I, PC, SP, stack, delay timer, sound timer, screen width and height.
+/// The screen follows, 1 bit per pixel.
+const HEADER_SIZE: usize = 4 + 1 + MEMORY_SIZE + 16 + 2 + 2 + 2 + 16 * 2 + 1 + 1 + 1 + 1;
+
+#[derive(Debug)]
+#[cfg_attr(feature = "std", derive(Error))]
+pub enum SaveStateError {
+    #[cfg_attr(feature = "std", error("Not a savestate: the header magic does not match."))]
+    BadMagic,
+    #[cfg_attr(feature = "std", error("Unsupported savestate version `{0}`."))]
+    UnsupportedVersion(u8),
+    #[cfg_attr(feature = "std", error("Savestate is {found} bytes long but should be {expected}."))]
+    WrongLength { found: usize, expected: usize },
+    #[cfg_attr(feature = "std", error("Savestate has a {0}x{1} screen, which can't be packed into whole bytes."))]
+    BadResolution(u8, u8),
+    #[cfg_attr(feature = "std", error("Savestate has a stack pointer of `{0}`, past the end of the stack."))]
+    StackPointerOutOfBounds(u16),
+}
+
+impl<B: Bus> Chip8<B> {
+    /// Serializes the machine into a fixed-size, little-endian binary blob.
+    pub fn save_state_bytes(&self) -> Vec<u8> {
+        let screen = self.bus.screen();
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + screen.len() / 8);
+        bytes.extend_from_slice(&SAVESTATE_MAGIC);
+        bytes.push(SAVESTATE_VERSION);
+
+        bytes.extend((0..MEMORY_SIZE).map(|address| self.bus.read(address)));
+        bytes.extend_from_slice(&self.data_registers.values());
+        bytes.extend_from_slice(&self.address_register.read().to_le_bytes());
+        bytes.extend_from_slice(&self.program_counter.read().to_le_bytes());
+        bytes.extend_from_slice(&self.stack_pointer.to_le_bytes());
+        for entry in self.stack.iter() {
+            bytes.extend_from_slice(&entry.to_le_bytes());
+        }
+        bytes.push(self.bus.delay_timer().value());
+        bytes.push(self.bus.sound_timer().value());
+        bytes.push(screen.width() as u8);
+        bytes.push(screen.height() as u8);
+
+        for chunk in 0..screen.len() / 8 {
+            let mut packed = 0u8;
+            for bit in 0..8 {
+                if screen[chunk * 8 + bit] {
+                    packed |= 0x80 >> bit;
+                }
+            }
+            bytes.push(packed);
+        }
+
+        bytes
+    }
+
+    /// A 64-bit FNV-1a hash of `save_state_bytes`, for checking that two runs ended up
+    /// in the same state.
+    pub fn state_hash(&self) -> u64 {
+        self.save_state_bytes()
+            .iter()
+            .fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x0000_0100_0000_01b3))
+    }
+
+    /// Restores a blob produced by `save_state_bytes`, and flags a redraw of the restored
+    /// screen. The machine is left untouched if the blob is rejected.
+    ///
+    /// The program could have come from anywhere, so its length is forgotten and, with the
+    /// memory sanitizer on, all of memory counts as written.
+    pub fn load_state_bytes(&mut self, bytes: &[u8]) -> Result<(), Chip8Error> {
+        self.restore_state_bytes(bytes)?;
+        self.program_len = 0;
+        self.mark_initialized(0..MEMORY_SIZE);
+        // Whatever the frontend shows is from before the load.
+        self.should_draw = true;
+        Ok(())
+    }
+
+    /// The part of `load_state_bytes` that puts back what the blob holds, and nothing else.
+    pub(crate) fn restore_state_bytes(&mut self, bytes: &[u8]) -> Result<(), Chip8Error> {
+        if bytes.len() < 5 || bytes[..4] != SAVESTATE_MAGIC {
+            return Err(SaveStateError::BadMagic.into());
+        }
+        if bytes[4] != SAVESTATE_VERSION {
+            return Err(SaveStateError::UnsupportedVersion(bytes[4]).into());
+        }
+        if bytes.len() < HEADER_SIZE {
+            return Err(SaveStateError::WrongLength { found: bytes.len(), expected: HEADER_SIZE }.into());
+        }
+        let (width, height) = (bytes[HEADER_SIZE - 2], bytes[HEADER_SIZE - 1]);
+        let screen_size = width as usize * height as usize;
+        if screen_size == 0 || !screen_size.is_multiple_of(8) {
+            return Err(SaveStateError::BadResolution(width, height).into());
+        }
+        if bytes.len() != HEADER_SIZE + screen_size / 8 {
+            return Err(SaveStateError::WrongLength { found: bytes.len(), expected: HEADER_SIZE + screen_size / 8 }.into());
+        }
+
+        let mut cursor = 5;
+        let mut take = |count: usize| {
+            let slice = &bytes[cursor..cursor + count];
+            cursor += count;
+            slice
+        };
+        let read_u16 = |slice: &[u8]| u16::from_le_bytes([slice[0], slice[1]]);
+
+        let memory = take(MEMORY_SIZE);
+        let registers = take(16);
+        let address_register = read_u16(take(2));
+        let program_counter = read_u16(take(2));
+        let stack_pointer = read_u16(take(2));
+        let stack = take(16 * 2);
+        let delay_timer = take(1)[0];
+        let sound_timer = take(1)[0];
+        take(2);
+        let screen = take(screen_size / 8);
+
+        if stack_pointer as usize > self.stack.len() {
+            return Err(SaveStateError::StackPointerOutOfBounds(stack_pointer).into());
+        }
+
+        self.bus.load(0, memory);
+        self.invalidate_decode_cache();
+        for (idx, value) in registers.iter().enumerate() {
+            self.data_registers.write_idx(idx, *value)?;
+        }
+        self.address_register.write(address_register)?;
+        self.program_counter.write(program_counter)?;
+        let stack: Vec<u16> = stack.chunks(2).map(read_u16).collect();
+        self.set_stack(&stack, stack_pointer)?;
+        self.bus.delay_timer_mut().reset(delay_timer);
+        self.bus.sound_timer_mut().reset(sound_timer);
+
+        let pixels = self.bus.screen_mut();
+        pixels.resize(width as usize, height as usize);
+        for (chunk, packed) in screen.iter().enumerate() {
+            for bit in 0..8 {
+                pixels[chunk * 8 + bit] = packed & (0x80 >> bit) != 0;
+            }
+        }
+        pixels.flush();
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    fn running_machine() -> Result<Chip8, Chip8Error> {
+        let mut chip = Chip8::new();
+        chip.initialize();
+        chip.load_program_bytes(&[
+            0x60, 0x07, // V0 = 7
+            0xF0, 0x15, // delay timer = V0
+            0xA0, 0x00, // I = glyph for 0
+            0xD0, 0x05, // draw it at (7, 7)
+            0x22, 0x0C, // call 0x20C
+            0x00, 0x00,
+            0x6E, 0x42, // VE = 0x42
+        ]);
+        for _ in 0..6 {
+            chip.step()?;
+        }
+        Ok(chip)
+    }
+
+    #[test]
+    fn stack_round_trip_and_bad_stack_pointer() -> Result<(), Chip8Error> {
+        let mut chip = running_machine()?;
+        chip.set_stack(&[0x204, 0x31A, 0x402], 3)?;
+        let bytes = chip.save_state_bytes();
+
+        let mut restored = Chip8::new();
+        restored.load_state_bytes(&bytes)?;
+        assert_eq!(restored.call_stack(), &[0x204, 0x31A, 0x402]);
+        assert_eq!(restored.stack_slice(), chip.stack_slice());
+        assert_eq!(restored.stack_slice().len(), 16);
+
+        assert!(matches!(
+            restored.set_stack(&[], 17),
+            Err(Chip8Error::SaveState(SaveStateError::StackPointerOutOfBounds(17)))
+        ));
+        assert_eq!(restored.call_stack().len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn loading_a_state_forces_a_redraw() -> Result<(), Chip8Error> {
+        let mut chip = running_machine()?;
+        let bytes = chip.save_state_bytes();
+        chip.take_redraw();
+        assert!(!chip.take_redraw());
+
+        chip.load_state_bytes(&bytes)?;
+        assert!(chip.take_redraw());
+        Ok(())
+    }
+
+    #[test]
+    fn savestate_round_trip() -> Result<(), Chip8Error> {
+        let chip = running_machine()?;
+        let bytes = chip.save_state_bytes();
+        assert_eq!(bytes.len(), HEADER_SIZE + 64 * 32 / 8);
+
+        let mut restored = Chip8::new();
+        restored.load_state_bytes(&bytes)?;
+
+        assert_eq!(restored.register_snapshot(), chip.register_snapshot());
+        assert_eq!(restored.call_stack(), chip.call_stack());
+        assert_eq!(restored.bus().delay_timer().value(), 7);
+        for address in 0..MEMORY_SIZE {
+            assert_eq!(restored.bus().read(address), chip.bus().read(address));
+        }
+        assert_eq!(restored.bus().screen(), chip.bus().screen());
+        assert_eq!(restored.save_state_bytes(), bytes);
+        Ok(())
+    }
+
+    #[test]
+    fn corrupted_header_is_rejected() -> Result<(), Chip8Error> {
+        let mut bytes = running_machine()?.save_state_bytes();
+        let mut chip = Chip8::new();
+
+        bytes[4] = SAVESTATE_VERSION + 1;
+        assert!(matches!(
+            chip.load_state_bytes(&bytes),
+            Err(Chip8Error::SaveState(SaveStateError::UnsupportedVersion(_)))
+        ));
+
+        bytes[0] = b'X';
+        assert!(matches!(
+            chip.load_state_bytes(&bytes),
+            Err(Chip8Error::SaveState(SaveStateError::BadMagic))
+        ));
+        assert_eq!(chip.register_snapshot(), Chip8::new().register_snapshot());
+        Ok(())
+    }
+}
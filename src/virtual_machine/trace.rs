@@ -0,0 +1,175 @@
+use alloc::{collections::VecDeque, vec::Vec};
+
+use super::{OpCode, OpKind};
+
+
+/// A single executed instruction, with the data registers as they were right after it ran.
+#[derive(Debug, Clone, Copy)]
+pub struct TraceEntry {
+    pub pc: u16,
+    pub opcode: OpCode,
+    pub registers: [u8; 16],
+}
+
+impl TraceEntry {
+    /// Writes the entry as a single JSON object followed by a newline.
+    #[cfg(feature = "std")]
+    pub fn write_json<W: std::io::Write>(&self, mut writer: W) -> std::io::Result<()> {
+        write!(
+            writer,
+            "{{\"pc\":{},\"opcode\":{},\"mnemonic\":\"{}\",\"regs\":[",
+            self.pc, self.opcode.value, self.opcode
+        )?;
+        for (idx, value) in self.registers.iter().enumerate() {
+            if idx > 0 {
+                write!(writer, ",")?;
+            }
+            write!(writer, "{}", value)?;
+        }
+        writeln!(writer, "]}}")
+    }
+}
+
+/// Ring buffer holding the most recently executed instructions.
+#[derive(Debug, Clone)]
+pub struct Trace {
+    entries: VecDeque<TraceEntry>,
+    capacity: usize,
+    only: Option<Vec<OpKind>>,
+}
+
+impl Trace {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+            only: None,
+        }
+    }
+
+    /// From now on, only record opcodes of the given kinds.
+    pub fn only(&mut self, kinds: &[OpKind]) {
+        self.only = Some(kinds.to_vec());
+    }
+
+    /// Go back to recording every opcode.
+    pub fn record_all(&mut self) {
+        self.only = None;
+    }
+
+    pub fn records(&self, kind: OpKind) -> bool {
+        self.only.as_ref().is_none_or(|kinds| kinds.contains(&kind))
+    }
+
+    pub fn record(&mut self, entry: TraceEntry) {
+        if self.capacity == 0 || !self.records(entry.opcode.kind) {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    /// Oldest entry first.
+    pub fn iter(&self) -> impl Iterator<Item = &TraceEntry> {
+        self.entries.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::virtual_machine::Chip8;
+
+    /// Pulls the raw text of `"key":<value>` out of a flat JSON object.
+    fn json_field<'a>(line: &'a str, key: &str) -> &'a str {
+        let start = line.find(&format!("\"{}\":", key)).unwrap() + key.len() + 3;
+        let rest = &line[start..];
+        if rest.starts_with('[') {
+            &rest[..=rest.find(']').unwrap()]
+        } else if let Some(stripped) = rest.strip_prefix('"') {
+            &stripped[..stripped.find('"').unwrap()]
+        } else {
+            &rest[..rest.find([',', '}']).unwrap()]
+        }
+    }
+
+    #[test]
+    fn ring_buffer_drops_oldest() -> Result<(), Box<dyn std::error::Error>> {
+        let mut trace = Trace::with_capacity(2);
+        for pc in [0x200, 0x202, 0x204] {
+            trace.record(TraceEntry { pc, opcode: OpCode::try_from(0x00E0)?, registers: [0; 16] });
+        }
+        let pcs: Vec<u16> = trace.iter().map(|entry| entry.pc).collect();
+        assert_eq!(pcs, vec![0x202, 0x204]);
+        Ok(())
+    }
+
+    #[test]
+    fn kind_filter_excludes_other_opcodes() -> Result<(), Box<dyn std::error::Error>> {
+        let mut chip = Chip8::new();
+        chip.initialize();
+        chip.enable_trace(16);
+        chip.trace_only(&[OpKind::Flow]);
+        chip.load_program_bytes(&[
+            0x60, 0x05, // LD V0, 5
+            0x80, 0x14, // ADD V0, V1
+            0x22, 0x08, // CALL 0x208
+            0x00, 0x00,
+            0x00, 0xEE, // RET
+        ]);
+        for _ in 0..4 {
+            chip.step()?;
+        }
+
+        let recorded: Vec<u16> = chip.trace().unwrap().iter().map(|entry| entry.opcode.value).collect();
+        assert_eq!(recorded, vec![0x2208, 0x00EE]);
+        Ok(())
+    }
+
+    #[test]
+    fn trace_to_writer_emits_json_lines() -> Result<(), Box<dyn std::error::Error>> {
+        let mut chip = Chip8::new();
+        chip.initialize();
+        chip.enable_trace(16);
+        chip.load_program_bytes(&[0x60, 0x2A, 0x71, 0x01]);
+
+        chip.step()?;
+        chip.step()?;
+
+        let mut buffer = Vec::new();
+        chip.trace_to_writer(&mut buffer)?;
+        let output = String::from_utf8(buffer)?;
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+
+        assert_eq!(json_field(lines[0], "pc").parse::<u16>()?, 0x200);
+        assert_eq!(json_field(lines[0], "opcode").parse::<u16>()?, 0x602A);
+        assert_eq!(json_field(lines[0], "mnemonic"), "LD V0, 0x2a");
+
+        assert_eq!(json_field(lines[1], "pc").parse::<u16>()?, 0x202);
+        assert_eq!(json_field(lines[1], "mnemonic"), "ADD V1, 0x01");
+        let registers: Vec<u8> = json_field(lines[1], "regs")
+            .trim_matches(['[', ']'])
+            .split(',')
+            .map(|value| value.parse())
+            .collect::<Result<_, _>>()?;
+        assert_eq!(registers.len(), 16);
+        assert_eq!(&registers[..2], &[0x2A, 0x01]);
+        Ok(())
+    }
+}
@@ -0,0 +1,81 @@
+use super::{Bus, Chip8, OpCode, OpLiteral};
+
+
+/// What the program has done with VF lately, see `Chip8::with_vf_tripwire`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum VfUse {
+    /// Nothing the program put in VF has been lost.
+    Untouched,
+    /// The program stored a value of its own in VF.
+    Scratch,
+    /// The instruction at this address overwrote that value with a flag.
+    Clobbered(u16),
+}
+
+/// Whether `opcode` sets VF as a carry, borrow, shifted out bit or collision flag.
+fn sets_flag(opcode: &OpCode) -> bool {
+    matches!(
+        opcode.literal,
+        OpLiteral::_8XY4 | OpLiteral::_8XY5 | OpLiteral::_8XY6 | OpLiteral::_8XY7 | OpLiteral::_8XYE | OpLiteral::_DXYN
+    )
+}
+
+/// Whether `opcode` stores a value of the program's choosing in VF.
+fn stores_in_vf(opcode: &OpCode) -> bool {
+    let x_is_vf = opcode.x() == 0xF;
+    match opcode.literal {
+        OpLiteral::_6XNN | OpLiteral::_7XNN | OpLiteral::_8XY0 | OpLiteral::_8XY1 | OpLiteral::_8XY2
+        | OpLiteral::_8XY3 | OpLiteral::_CXNN | OpLiteral::_FX07 | OpLiteral::_FX0A | OpLiteral::_FX65
+        | OpLiteral::_FX85 => x_is_vf,
+        _ => false,
+    }
+}
+
+/// Whether `opcode` uses the value in VF.
+fn reads_vf(opcode: &OpCode) -> bool {
+    let (x_is_vf, y_is_vf) = (opcode.x() == 0xF, opcode.y() == 0xF);
+    match opcode.literal {
+        OpLiteral::_3XNN | OpLiteral::_4XNN | OpLiteral::_7XNN | OpLiteral::_EX9E | OpLiteral::_EXA1
+        | OpLiteral::_FX15 | OpLiteral::_FX18 | OpLiteral::_FX1E | OpLiteral::_FX29 | OpLiteral::_FX33
+        | OpLiteral::_FX55 | OpLiteral::_FX75 => x_is_vf,
+        OpLiteral::_8XY0 => y_is_vf,
+        OpLiteral::_5XY0 | OpLiteral::_9XY0 | OpLiteral::_8XY1 | OpLiteral::_8XY2 | OpLiteral::_8XY3
+        | OpLiteral::_8XY4 | OpLiteral::_8XY5 | OpLiteral::_8XY6 | OpLiteral::_8XY7 | OpLiteral::_8XYE
+        | OpLiteral::_DXYN => x_is_vf || y_is_vf,
+        _ => false,
+    }
+}
+
+impl<B: Bus> Chip8<B> {
+    /// Log a warning whenever the program reads VF after an instruction replaced a value it
+    /// stored there with a flag. Purely a debugging aid for ROMs that use VF as scratch space;
+    /// VF is still overwritten exactly as before.
+    pub fn with_vf_tripwire(mut self) -> Self {
+        self.vf_tripwire = Some(VfUse::Untouched);
+        self
+    }
+
+    /// Called with each instruction right before it runs.
+    pub(crate) fn check_vf_tripwire(&mut self, pc: u16, opcode: &OpCode) {
+        let Some(vf_use) = self.vf_tripwire else {
+            return;
+        };
+        if let VfUse::Clobbered(at) = vf_use {
+            if reads_vf(opcode) {
+                log::warn!(
+                    "VF is read at {:#05x}, but the flag set at {:#05x} replaced what the program stored in it.",
+                    pc, at
+                );
+                self.vf_tripwire = Some(VfUse::Untouched);
+                return;
+            }
+        }
+        self.vf_tripwire = Some(if sets_flag(opcode) && vf_use != VfUse::Untouched {
+            VfUse::Clobbered(pc)
+        } else if stores_in_vf(opcode) {
+            VfUse::Scratch
+        } else {
+            vf_use
+        });
+    }
+}
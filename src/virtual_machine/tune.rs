@@ -0,0 +1,104 @@
+use super::{Bus, Chip8, Renderer, Screen};
+
+
+/// Clock speeds `Chip8::auto_tune` tries, slowest first.
+const CANDIDATE_CLOCKS: [u32; 12] = [200, 300, 400, 500, 600, 700, 800, 1000, 1200, 1500, 2000, 3000];
+
+/// How many frames each candidate clock runs for, i.e. a second of play.
+const TRIAL_FRAMES: u32 = 60;
+
+/// What `Chip8::auto_tune` should aim for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutoTuneGoal {
+    /// A redraw every frame, which is what action games are written for.
+    Action,
+    /// At least this many redraws per second, for games that are happy with less.
+    DrawsPerSecond(u32),
+}
+
+impl AutoTuneGoal {
+    fn draws_per_second(&self) -> u32 {
+        match self {
+            Self::Action => 60,
+            Self::DrawsPerSecond(draws) => (*draws).min(60),
+        }
+    }
+}
+
+/// Counts the frames that had something new to show.
+#[derive(Default)]
+struct DrawCounter(u32);
+
+impl Renderer for DrawCounter {
+    fn render(&mut self, _screen: &Screen) {
+        self.0 += 1;
+    }
+}
+
+impl<B: Bus> Chip8<B> {
+    /// Suggests a `clock_hz` for the loaded program. Each candidate clock gets a second of play on
+    /// a copy of the machine, starting from where this one is, and the slowest that redraws close
+    /// enough to as often as `goal` asks wins. Failing that, whichever redrew the most.
+    ///
+    /// Games that wait on the delay timer stop redrawing more often once they're fast enough to
+    /// finish a frame's work in a frame, so this finds the speed they were written for. The
+    /// machine itself is left alone; only its state, quirks and display carry over to the copy.
+    pub fn auto_tune(&self, goal: AutoTuneGoal) -> u32 {
+        let state = self.save_state_bytes();
+        let wanted = goal.draws_per_second() * TRIAL_FRAMES / 60 * 9 / 10;
+
+        let mut best = (0, self.clock_hz);
+        for clock_hz in CANDIDATE_CLOCKS {
+            let Ok(trial) = Chip8::new().with_quirks(self.quirks).with_display(self.display) else {
+                return self.clock_hz;
+            };
+            let mut trial = trial.with_clock_hz(clock_hz);
+            if trial.load_state_bytes(&state).is_err() {
+                return self.clock_hz;
+            }
+            trial.should_draw = false;
+            // Frames are a sixtieth of a second apart however fast they really run.
+            trial.clock = None;
+
+            let mut draws = DrawCounter::default();
+            for _ in 0..TRIAL_FRAMES {
+                if !matches!(trial.frame_advance(&mut draws, &mut ()), Ok(None)) {
+                    break;
+                }
+            }
+            if draws.0 >= wanted {
+                return clock_hz;
+            }
+            if draws.0 > best.0 {
+                best = (draws.0, clock_hz);
+            }
+        }
+        best.1
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use super::super::Chip8Error;
+
+    #[test]
+    fn timer_paced_rom_gets_a_plausible_clock() -> Result<(), Chip8Error> {
+        let chip = Chip8::from_rom_bytes(&[
+            0x61, 0x01, // V1 = 1
+            0xF1, 0x15, // delay timer = V1
+            0xD2, 0x25, // draw the glyph for 0
+            0xF0, 0x07, // V0 = delay timer
+            0x30, 0x00, // skip the next instruction once it's run out
+            0x12, 0x06, // keep waiting
+            0x12, 0x00, // next frame
+        ])?;
+
+        let clock_hz = chip.auto_tune(AutoTuneGoal::Action);
+        assert!((300..=1000).contains(&clock_hz), "{}", clock_hz);
+        assert!(chip.auto_tune(AutoTuneGoal::DrawsPerSecond(20)) <= clock_hz);
+        assert_eq!(chip.cycles(), 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,189 @@
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use thiserror::Error;
+
+use super::{Bus, Chip8, Chip8Error, OpCode, OpLiteral, MEMORY_SIZE};
+
+
+/// Programs are loaded at 0x200, so this is all the room there is.
+pub const MAX_ROM_SIZE: usize = 4096 - 0x200;
+
+/// How the 16-bit instruction words of a ROM appear to be laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteOrder {
+    /// The normal CHIP-8 layout, high byte first.
+    BigEndian,
+    /// Every pair of bytes looks swapped, see `Chip8::load_program_swapped`.
+    Swapped,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "std", derive(Error))]
+pub enum RomError {
+    #[cfg_attr(feature = "std", error("ROM is {0} bytes long, more than fit in memory after the entry point."))]
+    TooLarge(usize),
+    #[cfg_attr(feature = "std", error("This looks like a {0} file, not a ROM."))]
+    NotARom(&'static str),
+}
+
+/// Something suspicious about a ROM that doesn't stop it from loading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RomWarning {
+    /// More instructions decode with each word's bytes swapped than without.
+    LikelyByteSwapped,
+    /// The length is odd, so the last byte can't be a whole instruction. Usually trailing
+    /// metadata, or a truncated download.
+    OddLength,
+}
+
+/// Magic numbers of files that sometimes get mistaken for ROMs, and what they are.
+const FOREIGN_MAGICS: [(&[u8], &str); 9] = [
+    (b"PK\x03\x04", "zip"),
+    (b"\x1f\x8b\x08", "gzip"),
+    (b"7z\xbc\xaf\x27\x1c", "7z"),
+    (b"Rar!\x1a\x07", "rar"),
+    (b"\x89PNG\r\n\x1a\n", "PNG"),
+    (b"GIF8", "GIF"),
+    (b"\xff\xd8\xff", "JPEG"),
+    (b"%PDF", "PDF"),
+    (b"\x7fELF", "ELF"),
+];
+
+/// How many words in `words` look like real instructions. Bare `0NNN` machine code
+/// calls decode from almost anything starting with a zero, so they don't count.
+fn plausible_opcodes(words: impl Iterator<Item = u16>) -> usize {
+    words
+        .filter(|word| matches!(
+            OpCode::try_from(*word),
+            Ok(opcode) if opcode.literal != OpLiteral::_0NNN
+        ))
+        .count()
+}
+
+impl Chip8 {
+    /// Guesses whether `bytes` is a normal ROM or one with each instruction word byte-swapped.
+    pub fn detect_byte_order(bytes: &[u8]) -> ByteOrder {
+        let words = || bytes.chunks_exact(2);
+        let big_endian = plausible_opcodes(words().map(|word| u16::from_be_bytes([word[0], word[1]])));
+        let swapped = plausible_opcodes(words().map(|word| u16::from_le_bytes([word[0], word[1]])));
+
+        if swapped > big_endian {
+            ByteOrder::Swapped
+        } else {
+            ByteOrder::BigEndian
+        }
+    }
+
+    /// Checks that `bytes` can be loaded, and lists anything that looks off about it.
+    pub fn validate_rom(bytes: &[u8]) -> Result<Vec<RomWarning>, Chip8Error> {
+        if bytes.len() > MAX_ROM_SIZE {
+            return Err(RomError::TooLarge(bytes.len()).into());
+        }
+        if let Some((_, format)) = FOREIGN_MAGICS.iter().find(|(magic, _)| bytes.starts_with(magic)) {
+            return Err(RomError::NotARom(format).into());
+        }
+
+        let mut warnings = Vec::new();
+        if Self::detect_byte_order(bytes) == ByteOrder::Swapped {
+            warnings.push(RomWarning::LikelyByteSwapped);
+        }
+        if bytes.len() % 2 == 1 {
+            warnings.push(RomWarning::OddLength);
+        }
+        Ok(warnings)
+    }
+}
+
+impl<B: Bus> Chip8<B> {
+    /// `load_program_bytes`, after `Chip8::validate_rom` had no objections and the program was
+    /// found to fit between the entry point and the end of memory. Warnings are logged as well
+    /// as returned.
+    pub fn load_program_bytes_validated(&mut self, program: &[u8]) -> Result<Vec<RomWarning>, Chip8Error> {
+        let warnings = Chip8::validate_rom(program)?;
+        if self.entry_point as usize + program.len() > MEMORY_SIZE {
+            return Err(RomError::TooLarge(program.len()).into());
+        }
+        for warning in &warnings {
+            log::warn!("Loading a ROM anyway: {:?}", warning);
+        }
+        self.load_program_bytes(program);
+        Ok(warnings)
+    }
+
+    /// Loads a ROM whose instruction words were stored low byte first.
+    pub fn load_program_swapped(&mut self, program: &[u8]) {
+        let mut swapped = program.to_vec();
+        for word in swapped.chunks_exact_mut(2) {
+            word.swap(0, 1);
+        }
+        self.load_program_bytes(&swapped);
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::virtual_machine::BundledRoms;
+
+    const PROGRAM: [u8; 10] = [
+        0x60, 0x05, // V0 = 5
+        0x61, 0x03, // V1 = 3
+        0xA2, 0x0A, // I = 0x20A
+        0xD0, 0x15, // draw
+        0x12, 0x08, // jump back to the draw
+    ];
+
+    #[test]
+    fn byte_swapped_rom_is_detected() -> Result<(), Chip8Error> {
+        let mut swapped = PROGRAM;
+        for word in swapped.chunks_exact_mut(2) {
+            word.swap(0, 1);
+        }
+
+        assert_eq!(Chip8::detect_byte_order(&PROGRAM), ByteOrder::BigEndian);
+        assert_eq!(Chip8::detect_byte_order(&swapped), ByteOrder::Swapped);
+        assert_eq!(Chip8::validate_rom(&swapped)?, vec![RomWarning::LikelyByteSwapped]);
+        for name in BundledRoms::names() {
+            assert!(Chip8::validate_rom(BundledRoms::get(name).unwrap())?.is_empty());
+        }
+
+        let mut chip = Chip8::new();
+        chip.load_program_swapped(&swapped);
+        for (offset, byte) in PROGRAM.iter().enumerate() {
+            assert_eq!(chip.bus().read(0x200 + offset), *byte);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn odd_length_warns_and_images_are_rejected() -> Result<(), Chip8Error> {
+        let mut chip = Chip8::new();
+        assert_eq!(chip.load_program_bytes_validated(&PROGRAM[..9])?, vec![RomWarning::OddLength]);
+        assert_eq!(chip.bus().read(0x208), 0x12);
+
+        let png = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n', 0x00, 0x00];
+        let mut chip = Chip8::new();
+        assert!(matches!(
+            chip.load_program_bytes_validated(&png),
+            Err(Chip8Error::Rom(RomError::NotARom("PNG")))
+        ));
+        assert_eq!(chip.bus().read(0x200), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn rom_has_to_fit_after_the_entry_point() -> Result<(), Chip8Error> {
+        let rom = [0x12; 3000];
+        let mut chip = Chip8::new().with_entry_point(0x600);
+        assert!(matches!(
+            chip.load_program_bytes_validated(&rom),
+            Err(Chip8Error::Rom(RomError::TooLarge(3000)))
+        ));
+        assert_eq!(chip.bus().read(0x600), 0);
+
+        chip.load_program_bytes_validated(&rom[..MEMORY_SIZE - 0x600])?;
+        assert_eq!(chip.bus().read(MEMORY_SIZE - 1), 0x12);
+        Ok(())
+    }
+}